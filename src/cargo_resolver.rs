@@ -1,19 +1,23 @@
+use std::collections::HashSet;
 use std::sync::OnceLock;
 use std::task::Poll;
 
 use anyhow::bail;
 use cargo::core::dependency::DepKind;
-use cargo::core::resolver::{self, ResolveOpts, VersionPreferences};
+use cargo::core::resolver::features::{CliFeatures, RequestedFeatures};
+use cargo::core::resolver::{self, ResolveOpts, VersionOrdering, VersionPreferences};
 use cargo::core::Resolve;
 use cargo::core::ResolveVersion;
 use cargo::core::SourceId;
 use cargo::core::{Dependency, PackageId, Registry, Summary};
 use cargo::sources::source::QueryKind;
 use cargo::sources::IndexSummary;
-use cargo::util::interning::InternedString;
-use cargo::util::{CargoResult, IntoUrl};
+use cargo::util::{CargoResult, GlobalContext, IntoUrl};
+use cargo_util_schemas::manifest::RustVersion;
 use itertools::Itertools;
 
+use crate::{names::Names, target::Target, RootFeatures, VersionsOrder};
+
 impl<'a> Registry for crate::Index<'a> {
     fn query(
         &mut self,
@@ -21,6 +25,12 @@ impl<'a> Registry for crate::Index<'a> {
         kind: QueryKind,
         f: &mut dyn FnMut(IndexSummary),
     ) -> Poll<CargoResult<()>> {
+        // Cargo queries the same requirement many times, so only distinct ones count, like PubGrub's lookups.
+        self.cargo_queries
+            .insert((dep.package_name(), dep.version_req().to_string()));
+        if self.over_budget(self.cargo_queries.len()) {
+            return Poll::Ready(Err(crate::SomeError.into()));
+        }
         if let Some(by_name) = self.crates.get(&dep.package_name()) {
             if let Some(past_result) = &self.past_result {
                 for past_ver in past_result
@@ -70,27 +80,118 @@ impl<'a> Registry for crate::Index<'a> {
     }
 }
 
+/// Resolves the same root as `pubgrub::resolve(dp, root.clone(), ver)`.
+///
+/// A `Names::Workspace` is resolved as one summary for each of its members.
 pub fn resolve<'c>(
-    name: InternedString,
+    root: &Names<'c>,
     ver: &semver::Version,
     dp: &mut crate::Index<'c>,
 ) -> CargoResult<Resolve> {
-    let Some(pack) = dp.crates.get(&name) else {
-        bail!("No package found named '{name}'");
+    let summaries = match root {
+        Names::Workspace(_) => dp
+            .workspace
+            .iter()
+            .map(|(member, ver)| root_summary(member, ver, dp))
+            .collect::<CargoResult<Vec<_>>>()?,
+        _ => vec![root_summary(root, ver, dp)?],
+    };
+    let rust_version = dp
+        .rust_version
+        .as_ref()
+        .map(|v| v.to_string().parse::<RustVersion>())
+        .transpose()?;
+    let mut version_prefs = VersionPreferences::default();
+    if dp.versions_order == VersionsOrder::Minimal {
+        version_prefs.version_ordering(VersionOrdering::MinimumVersionsFirst);
+    }
+    if let Some(rust_version) = &rust_version {
+        version_prefs.rust_versions(vec![rust_version.clone()]);
+    }
+    for (name, vers) in dp.prefer_locked.into_iter().flatten() {
+        let Some(by_name) = dp.crates.get(name) else {
+            continue;
+        };
+        for (_, summary) in vers.iter().filter_map(|v| by_name.get(v)) {
+            version_prefs.prefer_package_id(summary.package_id());
+        }
+    }
+    let resolve_version = ResolveVersion::with_rust_version(rust_version.as_ref());
+    if dp.versions_order == VersionsOrder::DirectMinimal {
+        // The resolver only reads `-Z direct-minimal-versions` from a `GlobalContext`.
+        return DIRECT_MINIMAL_VERSIONS.with(|gctx| {
+            let gctx = gctx.as_ref().map_err(|e| anyhow::anyhow!("{e:#}"))?;
+            resolver::resolve(
+                &summaries,
+                &[],
+                dp,
+                &version_prefs,
+                resolve_version,
+                Some(gctx),
+            )
+        });
+    }
+    resolver::resolve(&summaries, &[], dp, &version_prefs, resolve_version, None)
+}
+
+thread_local! {
+    static DIRECT_MINIMAL_VERSIONS: CargoResult<GlobalContext> = direct_minimal_versions_context();
+}
+
+/// A quiet, offline `GlobalContext` with `-Z direct-minimal-versions`.
+fn direct_minimal_versions_context() -> CargoResult<GlobalContext> {
+    let mut gctx = GlobalContext::default()?;
+    gctx.nightly_features_allowed = true;
+    gctx.configure(
+        0,
+        true,
+        None,
+        false,
+        false,
+        true,
+        &None,
+        &["direct-minimal-versions".to_string()],
+        &[],
+    )?;
+    Ok(gctx)
+}
+
+/// The summary of a root, moved out of the registry it depends on, and the options to resolve it with.
+fn root_summary(
+    root: &Names,
+    ver: &semver::Version,
+    dp: &crate::Index,
+) -> CargoResult<(Summary, ResolveOpts)> {
+    let summary: Summary = match root {
+        &Names::Root(name) => {
+            let Some(manifest) = dp.root_manifest(name) else {
+                bail!("No root manifest named '{name}'");
+            };
+            summary_for_target(manifest, dp.target)?
+        }
+        &Names::Bucket(name, _, true) => {
+            let Some(pack) = dp.crates.get(&name) else {
+                bail!("No package found named '{name}'");
+            };
+            let Some((_, summary)) = pack.get(ver).cloned() else {
+                bail!("No version found for package '{name}@{ver}'");
+            };
+            summary
+        }
+        _ => bail!("'{root}' is not a root"),
     };
-    let Some((_, summary)) = pack.get(ver).cloned() else {
-        bail!("No version found for package '{name}@{ver}'");
+    let features = match &dp.root_features {
+        RootFeatures::All => CliFeatures::new_all(true),
+        RootFeatures::Default => CliFeatures::from_command_line(&[], false, true)?,
+        RootFeatures::NoDefault => CliFeatures::from_command_line(&[], false, false)?,
+        RootFeatures::List(list) => {
+            let list = list.iter().map(|f| f.to_string()).collect_vec();
+            CliFeatures::from_command_line(&list, false, false)?
+        }
     };
+    let opts = ResolveOpts::new(dp.root_dev_deps, RequestedFeatures::CliFeatures(features));
     let new_id = summary.package_id().with_source_id(other_registry_loc());
-    let summary = summary.override_id(new_id);
-    resolver::resolve(
-        &[(summary, ResolveOpts::everything())],
-        &[],
-        dp,
-        &VersionPreferences::default(),
-        ResolveVersion::with_rust_version(None),
-        None,
-    )
+    Ok((summary.override_id(new_id), opts))
 }
 
 impl From<&crate::index_data::Dependency> for Dependency {
@@ -115,23 +216,64 @@ impl From<&crate::index_data::Dependency> for Dependency {
 impl TryFrom<&crate::index_data::Version> for Summary {
     type Error = anyhow::Error;
     fn try_from(value: &crate::index_data::Version) -> Result<Self, Self::Error> {
-        let pid = PackageId::new(value.name, (*value.vers).clone(), registry_loc());
-        let dep = value.deps.iter().map(|d| d.into()).collect_vec();
-        let features = value
-            .features
-            .iter()
-            .map(|(&f, v)| (f, v.iter().copied().collect()))
-            .collect();
-        Summary::new(pid, dep, &features, value.links, None)
+        summary_for_target(value, None)
     }
 }
 
+/// Like `Summary::try_from`, but without the dependencies that are not used on `target`.
+///
+/// Cargo resolves for every platform, so this is the only way to have it resolve for one.
+/// Feature values that name a dependency left out are left out as well, as cargo would reject them.
+pub fn summary_for_target(
+    value: &crate::index_data::Version,
+    target: Option<&Target>,
+) -> CargoResult<Summary> {
+    let pid = PackageId::new(value.name, (*value.vers).clone(), registry_loc());
+    let on_target = |d: &&crate::index_data::Dependency| {
+        d.target
+            .zip(target)
+            .map_or(true, |(on, target)| target.matches(&on))
+    };
+    let names: HashSet<&str> = value
+        .deps
+        .iter()
+        .filter(on_target)
+        .map(|d| d.name.as_str())
+        .collect();
+    let dep = value
+        .deps
+        .iter()
+        .filter(on_target)
+        .map(|d| d.into())
+        .collect_vec();
+    let features = value
+        .features
+        .iter()
+        .map(|(&f, v)| {
+            let v = v.iter().copied().filter(|v| {
+                let dep = v
+                    .strip_prefix("dep:")
+                    .or_else(|| v.split_once('/').map(|(d, _)| d.trim_end_matches('?')));
+                dep.map_or(true, |d| names.contains(d))
+            });
+            (f, v.collect())
+        })
+        .collect();
+    let rust_version = value.rust_version.and_then(|r| r.parse().ok());
+    Summary::new(pid, dep, &features, value.links, rust_version)
+}
+
 fn registry_loc() -> SourceId {
     static EXAMPLE_DOT_COM: OnceLock<SourceId> = OnceLock::new();
     *EXAMPLE_DOT_COM
         .get_or_init(|| SourceId::for_registry(&"https://example.com".into_url().unwrap()).unwrap())
 }
 
+/// Whether `id` is one of the roots, which `resolve` moves out of the registry they depend on.
+pub fn is_root(id: PackageId) -> bool {
+    id.source_id() == other_registry_loc()
+}
+
 fn other_registry_loc() -> SourceId {
     static OTHER_EXAMPLE_DOT_COM: OnceLock<SourceId> = OnceLock::new();
     *OTHER_EXAMPLE_DOT_COM.get_or_init(|| {
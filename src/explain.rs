@@ -0,0 +1,192 @@
+//! Explains a PubGrub `NoSolution` in terms of crates and features.
+//!
+//! The `Names` encoding adds synthetic packages (Wide, BucketFeatures, Links, Cycle, ...)
+//! which make the `DefaultStringReporter` output hard to read and impossible to compare to cargo.
+//! This walks the derivation tree, drops the incompatibilities that only exist because of the encoding,
+//! and describes the rest the way a user would think about them.
+
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+use itertools::Itertools as _;
+use pubgrub::{DerivationTree, External, VersionSet};
+use semver_pubgrub::{SemverCompatibility, SemverPubgrub};
+
+use crate::{
+    names::{new_bucket, FeatureNamespace, Names},
+    rc_semver_pubgrub::RcSemverPubgrub,
+};
+
+pub fn explain(tree: &DerivationTree<Names, RcSemverPubgrub, String>) -> String {
+    let mut facts = Vec::new();
+    collect(tree, &mut facts, &mut HashSet::new());
+    let mut lines = facts.iter().map(|f| f.to_string()).unique().collect_vec();
+
+    let mut links: BTreeMap<&str, BTreeSet<String>> = BTreeMap::new();
+    for f in &facts {
+        if let Fact::Links(p, vs, l) = f {
+            links.entry(*l).or_default().insert(term(p, vs));
+        }
+    }
+    for (l, users) in links {
+        if users.len() > 1 {
+            lines.push(format!(
+                "links = \"{l}\" conflict: only one of {} can be selected",
+                users.iter().join(", ")
+            ));
+        }
+    }
+
+    let mut cycles: BTreeMap<usize, BTreeSet<String>> = BTreeMap::new();
+    for f in &facts {
+        if let Fact::InCycle(p, vs, id) = f {
+            cycles.entry(*id).or_default().insert(term(p, vs));
+        }
+    }
+    for members in cycles.values() {
+        lines.push(format!(
+            "cyclic package dependency: {} can not all be selected",
+            members.iter().join(", ")
+        ));
+    }
+    lines.join("\n")
+}
+
+enum Fact<'a, 'c> {
+    NoVersions(&'a Names<'c>, &'a RcSemverPubgrub),
+    Unavailable(&'a Names<'c>, &'a RcSemverPubgrub, &'a str),
+    DependsOn(
+        &'a Names<'c>,
+        &'a RcSemverPubgrub,
+        &'a Names<'c>,
+        &'a RcSemverPubgrub,
+    ),
+    Links(&'a Names<'c>, &'a RcSemverPubgrub, &'a str),
+    InCycle(&'a Names<'c>, &'a RcSemverPubgrub, usize),
+}
+
+/// Collects the facts of `tree`, visiting each shared subtree once, as the same one can be reached many times.
+fn collect<'a, 'c>(
+    tree: &'a DerivationTree<Names<'c>, RcSemverPubgrub, String>,
+    facts: &mut Vec<Fact<'a, 'c>>,
+    shared: &mut HashSet<usize>,
+) {
+    match tree {
+        DerivationTree::External(External::NotRoot(_, _)) => {}
+        DerivationTree::External(External::NoVersions(p, vs)) => {
+            if !matches!(p, Names::Links(_) | Names::Cycle(_, _)) {
+                facts.push(Fact::NoVersions(p, vs));
+            }
+        }
+        DerivationTree::External(External::Custom(p, vs, m)) => {
+            facts.push(Fact::Unavailable(p, vs, m));
+        }
+        DerivationTree::External(External::FromDependencyOf(p1, vs1, p2, vs2)) => match p2 {
+            Names::Links(l) => facts.push(Fact::Links(p1, vs1, l.as_str())),
+            Names::Cycle(_, id) => facts.push(Fact::InCycle(p1, vs1, *id)),
+            // Every synthetic package depends on the real package of the same crate,
+            // which is not something a user wrote down, unlike a crate depending on another version of itself.
+            _ if !is_root(p1) && p1.crate_() == p2.crate_() && is_encoding_edge(p1, p2) => {}
+            _ => facts.push(Fact::DependsOn(p1, vs1, p2, vs2)),
+        },
+        DerivationTree::Derived(d) => {
+            if d.shared_id.is_some_and(|id| !shared.insert(id)) {
+                return;
+            }
+            collect(&d.cause1, facts, shared);
+            collect(&d.cause2, facts, shared);
+        }
+    }
+}
+
+impl std::fmt::Display for Fact<'_, '_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Fact::NoVersions(p, vs) => write!(f, "no versions of {} exist", term(p, vs)),
+            Fact::Unavailable(p, vs, m) => match p {
+                Names::BucketFeatures(n, c, FeatureNamespace::Feat(feat)) => {
+                    write!(f, "{} has no feature `{feat}`", crate_term(n, c, vs))
+                }
+                Names::BucketFeatures(n, c, FeatureNamespace::Dep(dep)) => write!(
+                    f,
+                    "{} has no optional dependency `{dep}`",
+                    crate_term(n, c, vs)
+                ),
+                _ => write!(f, "{} is unavailable: {m}", term(p, vs)),
+            },
+            Fact::DependsOn(p1, vs1, p2, vs2) => {
+                write!(f, "{} depends on {}", term(p1, vs1), term(p2, vs2))
+            }
+            Fact::Links(p, vs, l) => write!(f, "{} has links = \"{l}\"", term(p, vs)),
+            Fact::InCycle(p, vs, _) => {
+                write!(f, "{} is part of a cyclic package dependency", term(p, vs))
+            }
+        }
+    }
+}
+
+fn is_encoding_edge(p1: &Names, p2: &Names) -> bool {
+    match p1 {
+        Names::Bucket(n, c, _) => p2 == &new_bucket(*n, c.clone(), false),
+        _ => true,
+    }
+}
+
+fn is_root(p: &Names) -> bool {
+    matches!(
+        p,
+        Names::Bucket(_, _, true) | Names::Root(_) | Names::Workspace(_)
+    )
+}
+
+fn compat(c: &SemverCompatibility) -> String {
+    match c {
+        SemverCompatibility::Major(i) => format!("{i}.x"),
+        SemverCompatibility::Minor(i) => format!("0.{i}.x"),
+        SemverCompatibility::Patch(i) => format!("0.0.{i}"),
+    }
+}
+
+/// The versions, left out when they add nothing to what the package name already says.
+fn versions(vs: &RcSemverPubgrub, implied: Option<SemverPubgrub>) -> String {
+    if let Some(v) = vs.inner.as_singleton() {
+        format!(" {v}")
+    } else if vs == &RcSemverPubgrub::full() || Some(&*vs.inner) == implied.as_ref() {
+        String::new()
+    } else {
+        format!(" {vs}")
+    }
+}
+
+fn crate_term(n: &str, c: &SemverCompatibility, vs: &RcSemverPubgrub) -> String {
+    format!("crate `{n}` {}{}", compat(c), versions(vs, Some(c.into())))
+}
+
+fn term(p: &Names, vs: &RcSemverPubgrub) -> String {
+    match p {
+        Names::Root(n) | Names::Bucket(n, _, true) => {
+            format!("root crate `{n}`{}", versions(vs, None))
+        }
+        Names::Workspace(n) => format!("workspace `{n}`"),
+        Names::Bucket(n, c, false) => crate_term(n, c, vs),
+        Names::BucketFeatures(n, c, FeatureNamespace::Feat(feat)) => {
+            format!("{} with feature `{feat}`", crate_term(n, c, vs))
+        }
+        Names::BucketFeatures(n, c, FeatureNamespace::Dep(dep)) => {
+            format!("{} with optional dependency `{dep}`", crate_term(n, c, vs))
+        }
+        Names::BucketDefaultFeatures(n, c) => {
+            format!("{} with default features", crate_term(n, c, vs))
+        }
+        // The versions of a Wide package are only the canonical version of each compatibility range,
+        // so the requirement is more informative.
+        Names::Wide(n, req, from, _) => format!("crate `{n}` {req} (as required by `{from}`)"),
+        Names::WideFeatures(n, req, from, _, feat) => {
+            format!("crate `{n}` {req} with feature `{feat}` (as required by `{from}`)")
+        }
+        Names::WideDefaultFeatures(n, req, from, _) => {
+            format!("crate `{n}` {req} with default features (as required by `{from}`)")
+        }
+        Names::Links(l) => format!("links = \"{l}\""),
+        Names::Cycle(n, id) => format!("cyclic package dependency #{id} through `{n}`"),
+    }
+}
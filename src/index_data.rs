@@ -31,6 +31,9 @@ struct RawIndexDependency<'da> {
     #[serde(skip_serializing_if = "is_default")]
     #[serde(default)]
     optional: bool,
+    #[serde(skip_serializing_if = "is_default")]
+    #[serde(default)]
+    target: Option<&'da str>,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Hash)]
@@ -43,6 +46,37 @@ pub struct Dependency {
     pub default_features: bool,
     pub kind: crates_index::DependencyKind,
     pub optional: bool,
+    /// The platform it is only used on, a target triple or a `cfg(..)` expression, see `Target::matches`.
+    pub target: Option<InternedString>,
+}
+
+impl Dependency {
+    pub fn new(
+        name: InternedString,
+        package_name: InternedString,
+        req: semver::VersionReq,
+        features: Vec<InternedString>,
+        default_features: bool,
+        kind: crates_index::DependencyKind,
+        optional: bool,
+    ) -> Self {
+        let pubgrub_req: SemverPubgrub = (&req).into();
+        Self {
+            name,
+            package_name,
+            pubgrub_req: pubgrub_req.into(),
+            req: req.into(),
+            features: features.into(),
+            default_features,
+            kind,
+            optional,
+            target: None,
+        }
+    }
+
+    pub fn with_target(self, target: Option<InternedString>) -> Self {
+        Self { target, ..self }
+    }
 }
 
 impl<'da> From<RawIndexDependency<'da>> for Dependency {
@@ -66,6 +100,7 @@ impl<'da> From<RawIndexDependency<'da>> for Dependency {
             default_features: value.default_features,
             kind: value.kind,
             optional: value.optional,
+            target: value.target.map(|s| s.into()),
         }
     }
 }
@@ -89,6 +124,7 @@ impl<'a> Into<RawIndexDependency<'static>> for &'a Dependency {
             default_features: self.default_features,
             kind: self.kind,
             optional: self.optional,
+            target: self.target.map(|s| s.as_str()),
         }
     }
 }
@@ -114,6 +150,7 @@ impl TryFrom<&crates_index::Dependency> for Dependency {
             kind: dep.kind(),
             optional: dep.is_optional(),
             default_features: dep.has_default_features(),
+            target: dep.target().map(|s| s.into()),
         })
     }
 }
@@ -163,6 +200,18 @@ impl Into<Vec<RawIndexDependency<'static>>> for DependencyList {
     }
 }
 
+/// Parses a Rust version like `1.70` as `1.70.0`, so that it can be compared to other Rust versions.
+pub fn parse_rust_version(s: &str) -> Option<semver::Version> {
+    let mut parts = s.split('.').map(|p| p.parse::<u64>().ok());
+    let major = parts.next()??;
+    let minor = parts.next().unwrap_or(Some(0))?;
+    let patch = parts.next().unwrap_or(Some(0))?;
+    if parts.next().is_some() {
+        return None;
+    }
+    Some(semver::Version::new(major, minor, patch))
+}
+
 fn default_semver_version_for_serde() -> semver::Version {
     semver::Version::new(0, 0, 1)
 }
@@ -189,6 +238,9 @@ struct RawIndexVersion<'da> {
     #[serde(skip_serializing_if = "is_default")]
     #[serde(default)]
     yanked: bool,
+    #[serde(skip_serializing_if = "is_default")]
+    #[serde(default)]
+    rust_version: Option<&'da str>,
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, Eq)]
@@ -202,10 +254,48 @@ pub struct Version {
     pub features: Intern<BTreeMap<InternedString, Intern<BTreeSet<InternedString>>>>,
     pub links: Option<InternedString>,
     pub yanked: bool,
+    /// The oldest Rust it supports, as written in the index, like `1.70`. See `Version::msrv`.
+    pub rust_version: Option<InternedString>,
 }
 
-#[cfg(test)]
 impl Version {
+    pub fn new(
+        name: InternedString,
+        vers: semver::Version,
+        deps: Vec<Dependency>,
+        features: BTreeMap<InternedString, BTreeSet<InternedString>>,
+        links: Option<InternedString>,
+    ) -> Self {
+        let mut grouped: BTreeMap<InternedString, Vec<Dependency>> = BTreeMap::new();
+        for dep in deps {
+            grouped.entry(dep.name).or_default().push(dep);
+        }
+        Self {
+            name,
+            vers: vers.into(),
+            deps: DependencyList {
+                deps: grouped
+                    .into_iter()
+                    .map(|(k, v)| (k, Intern::new(v)))
+                    .collect(),
+            },
+            features_raw: Intern::new(Default::default()),
+            features: Intern::new(Default::default()),
+            links,
+            yanked: false,
+            rust_version: None,
+        }
+        .with_features_raw(
+            features
+                .into_iter()
+                .map(|(k, v)| (k, Intern::new(v)))
+                .collect(),
+        )
+    }
+    /// `rust_version` as a full version, see `parse_rust_version`.
+    pub fn msrv(&self) -> Option<semver::Version> {
+        self.rust_version.and_then(|r| parse_rust_version(&r))
+    }
     pub(crate) fn without_features(self) -> Option<Self> {
         if !self.features_raw.is_empty() {
             Some(Self {
@@ -219,50 +309,75 @@ impl Version {
     }
     pub(crate) fn without_a_feature(self, i: usize) -> Option<Self> {
         if !self.features_raw.is_empty() {
-            let features_raw: Intern<BTreeMap<InternedString, Intern<BTreeSet<InternedString>>>> =
-                Intern::new(
-                    self.features_raw
-                        .iter()
-                        .enumerate()
-                        .filter(|(v, _)| v != &i)
-                        .map(|(_, (f, d))| (f.clone(), d.clone()))
-                        .collect(),
-                );
-
-            let explicitly_named_deps: BTreeSet<&str> = features_raw
-                .values()
-                .flat_map(|f| f.iter())
-                .filter_map(|f| f.strip_prefix("dep:"))
+            let features_raw = self
+                .features_raw
+                .iter()
+                .enumerate()
+                .filter(|(v, _)| v != &i)
+                .map(|(_, (f, d))| (f.clone(), d.clone()))
                 .collect();
-
-            let mut features: BTreeMap<_, _> = (*features_raw).clone();
-            for dep in self.deps.iter() {
-                if explicitly_named_deps.contains(dep.name.as_str()) {
-                    continue;
-                }
-                if !dep.optional {
-                    continue;
-                }
-                if dep.kind == crates_index::DependencyKind::Dev {
-                    continue;
-                }
-                features.insert(
-                    dep.name.clone(),
-                    Intern::new(BTreeSet::from_iter([InternedString::new(&format!(
-                        "dep:{}",
-                        dep.name
-                    ))])),
-                );
-            }
-            Some(Self {
-                features_raw,
-                features: features.into(),
-                ..self
-            })
+            Some(self.with_features_raw(features_raw))
         } else {
             None
         }
     }
+    /// Removes the `j`th item listed by the `i`th feature.
+    pub(crate) fn without_a_feature_value(self, i: usize, j: usize) -> Option<Self> {
+        let (feature, values) = self.features_raw.iter().nth(i)?;
+        if j >= values.len() {
+            return None;
+        }
+        let mut features_raw = (*self.features_raw).clone();
+        features_raw.insert(
+            *feature,
+            Intern::new(
+                values
+                    .iter()
+                    .enumerate()
+                    .filter(|(v, _)| v != &j)
+                    .map(|(_, f)| *f)
+                    .collect(),
+            ),
+        );
+        Some(self.with_features_raw(features_raw))
+    }
+    fn with_features_raw(
+        self,
+        features_raw: BTreeMap<InternedString, Intern<BTreeSet<InternedString>>>,
+    ) -> Self {
+        let features_raw = Intern::new(features_raw);
+
+        let explicitly_named_deps: BTreeSet<&str> = features_raw
+            .values()
+            .flat_map(|f| f.iter())
+            .filter_map(|f| f.strip_prefix("dep:"))
+            .collect();
+
+        let mut features: BTreeMap<_, _> = (*features_raw).clone();
+        for dep in self.deps.iter() {
+            if explicitly_named_deps.contains(dep.name.as_str()) {
+                continue;
+            }
+            if !dep.optional {
+                continue;
+            }
+            if dep.kind == crates_index::DependencyKind::Dev {
+                continue;
+            }
+            features.insert(
+                dep.name.clone(),
+                Intern::new(BTreeSet::from_iter([InternedString::new(&format!(
+                    "dep:{}",
+                    dep.name
+                ))])),
+            );
+        }
+        Self {
+            features_raw,
+            features: features.into(),
+            ..self
+        }
+    }
     pub(crate) fn without_deps(self) -> Option<Self> {
         if !self.deps.deps.is_empty() {
             Some(Self {
@@ -292,6 +407,98 @@ impl Version {
             None
         }
     }
+    /// Applies `f` to the `i`th dependency, in the order of `DependencyList::iter`.
+    /// Returns `None` if that did not change anything.
+    pub(crate) fn with_a_dep(self, i: usize, f: impl FnOnce(&mut Dependency)) -> Option<Self> {
+        let mut dep = self.deps.iter().nth(i)?.clone();
+        let old = dep.clone();
+        f(&mut dep);
+        if dep == old {
+            return None;
+        }
+        // Regroup, as `f` may have changed the name.
+        let mut deps: BTreeMap<InternedString, Vec<Dependency>> = BTreeMap::new();
+        for (k, d) in self.deps.iter().enumerate() {
+            let d = if k == i { dep.clone() } else { d.clone() };
+            deps.entry(d.name).or_default().push(d);
+        }
+        let features_raw = (*self.features_raw).clone();
+        Some(
+            Self {
+                deps: DependencyList {
+                    deps: deps.into_iter().map(|(k, v)| (k, Intern::new(v))).collect(),
+                },
+                ..self
+            }
+            .with_features_raw(features_raw),
+        )
+    }
+    /// Replaces the requirement of the `i`th dependency with `req`.
+    pub(crate) fn with_a_req(self, i: usize, req: &semver::VersionReq) -> Option<Self> {
+        self.with_a_dep(i, |dep| {
+            let pubgrub_req: SemverPubgrub = req.into();
+            dep.pubgrub_req = pubgrub_req.into();
+            dep.req = req.clone().into();
+        })
+    }
+    /// Removes the `c`th comparator from the requirement of the `i`th dependency, as in `>=0.3, <0.5` to `>=0.3`.
+    pub(crate) fn without_a_comparator(self, i: usize, c: usize) -> Option<Self> {
+        let dep = self.deps.iter().nth(i)?;
+        // Removing the only comparator is the same as `with_a_req(i, "*")`.
+        if dep.req.comparators.len() < 2 || c >= dep.req.comparators.len() {
+            return None;
+        }
+        let mut req = (*dep.req).clone();
+        req.comparators.remove(c);
+        self.with_a_req(i, &req)
+    }
+    /// Turns off `default_features` of the `i`th dependency.
+    pub(crate) fn without_default_features(self, i: usize) -> Option<Self> {
+        self.with_a_dep(i, |dep| dep.default_features = false)
+    }
+    /// Names the `i`th dependency after the package it depends on, and the feature values that refer to it.
+    /// Returns `None` if another dependency or feature already has that name,
+    /// or another dependency has the same name, as the feature values refer to both.
+    pub(crate) fn without_a_rename(self, i: usize) -> Option<Self> {
+        let dep = self.deps.iter().nth(i)?;
+        let (old, new) = (dep.name, dep.package_name);
+        if old == new
+            || self.deps.get(&old).len() > 1
+            || !self.deps.get(&new).is_empty()
+            || self.features_raw.contains_key(&new)
+        {
+            return None;
+        }
+        // Without a feature of the same name, `old` is the implicit feature of an optional dependency.
+        let implicit_feature = !self.features_raw.contains_key(&old);
+        let rename = |value: &InternedString| {
+            if implicit_feature && *value == old {
+                return new;
+            }
+            if value.strip_prefix("dep:") == Some(old.as_str()) {
+                return InternedString::new(&format!("dep:{new}"));
+            }
+            if let Some((dep_name, feat)) = value.split_once('/') {
+                if let Some(weak) = dep_name.strip_suffix('?') {
+                    if weak == old.as_str() {
+                        return InternedString::new(&format!("{new}?/{feat}"));
+                    }
+                } else if dep_name == old.as_str() {
+                    return InternedString::new(&format!("{new}/{feat}"));
+                }
+            }
+            *value
+        };
+        let features_raw = self
+            .features_raw
+            .iter()
+            .map(|(f, values)| (*f, Intern::new(values.iter().map(&rename).collect())))
+            .collect();
+        Some(
+            self.with_a_dep(i, |dep| dep.name = dep.package_name)?
+                .with_features_raw(features_raw),
+        )
+    }
 }
 
 impl<'da> From<RawIndexVersion<'da>> for Version {
@@ -340,6 +547,7 @@ impl<'da> From<RawIndexVersion<'da>> for Version {
             features: features.into(),
             links: value.links.map(|s| s.into()),
             yanked: value.yanked,
+            rust_version: value.rust_version.map(|s| s.into()),
         }
     }
 }
@@ -357,6 +565,7 @@ impl Into<RawIndexVersion<'static>> for Version {
                 .collect(),
             links: self.links.map(|s| s.as_str()),
             yanked: self.yanked,
+            rust_version: self.rust_version.map(|s| s.as_str()),
         }
     }
 }
@@ -427,6 +636,7 @@ impl TryFrom<&crates_index::Version> for Version {
             features: features.into(),
             links: ver.links().map(|s| s.into()),
             yanked: ver.is_yanked(),
+            rust_version: ver.rust_version().map(|s| s.into()),
         })
     }
 }
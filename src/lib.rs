@@ -10,26 +10,35 @@ use std::{
     time::Instant,
 };
 
-use cargo::{core::Summary, util::interning::InternedString};
+use cargo::{
+    core::{Resolve, Summary},
+    util::{interning::InternedString, CargoResult},
+};
 use crates_index::DependencyKind;
 use either::Either;
 use hasher::StableHasher;
 use itertools::Itertools as _;
 use names::{new_bucket, new_links, new_wide, FeatureNamespace, Names};
 use pubgrub::{
-    resolve, Dependencies, DependencyConstraints, DependencyProvider, PubGrubError,
-    SelectedDependencies, VersionSet,
+    resolve, DefaultStringReporter, Dependencies, DependencyConstraints, DependencyProvider,
+    PubGrubError, Reporter as _, SelectedDependencies, VersionSet,
 };
 use rc_semver_pubgrub::RcSemverPubgrub;
 use ron::ser::PrettyConfig;
 use semver_pubgrub::{SemverCompatibility, SemverPubgrub};
+use target::Target;
 
 pub mod cargo_resolver;
+pub mod explain;
 pub mod hasher;
 pub mod index_data;
+pub mod lockfile;
+pub mod manifest;
+pub mod minimize;
 pub mod names;
 mod rc_semver_pubgrub;
 pub mod read_index;
+pub mod target;
 #[cfg(test)]
 mod tests;
 
@@ -44,8 +53,75 @@ static GLOBAL: tikv_jemallocator::Jemalloc = tikv_jemallocator::Jemalloc;
 #[global_allocator]
 static GLOBAL: mimalloc::MiMalloc = mimalloc::MiMalloc;
 
-const TIME_MAKE_FILE: f32 = 40.0;
-const TIME_CUT_OFF: f32 = TIME_MAKE_FILE * 4.0;
+#[derive(Clone, Copy, Debug)]
+pub struct Budget {
+    pub time_cut_off: f32,
+    pub time_make_file: f32,
+    pub max_get_dependencies: Option<usize>,
+}
+
+impl Default for Budget {
+    fn default() -> Self {
+        Self {
+            time_cut_off: 160.0,
+            time_make_file: 40.0,
+            max_get_dependencies: None,
+        }
+    }
+}
+
+/// The dev-dependencies of the root are used in every mode, unless `Index::with_root_dev_deps(false)`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum RootFeatures {
+    #[default]
+    All,
+    Default,
+    NoDefault,
+    List(Vec<InternedString>),
+}
+
+impl std::str::FromStr for RootFeatures {
+    type Err = std::convert::Infallible;
+
+    /// `all`, `default`, `no-default`, or a comma separated list of features.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "all" => RootFeatures::All,
+            "default" => RootFeatures::Default,
+            "no-default" => RootFeatures::NoDefault,
+            list => RootFeatures::List(
+                list.split(',')
+                    .map(str::trim)
+                    .filter(|f| !f.is_empty())
+                    .map(InternedString::new)
+                    .collect(),
+            ),
+        })
+    }
+}
+
+impl std::fmt::Display for RootFeatures {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RootFeatures::All => f.write_str("all"),
+            RootFeatures::Default => f.write_str("default"),
+            RootFeatures::NoDefault => f.write_str("no-default"),
+            RootFeatures::List(list) => f.write_str(&list.iter().join(",")),
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum VersionsOrder {
+    /// The newest, the default of cargo.
+    #[default]
+    Maximal,
+    /// The oldest, like `cargo -Z minimal-versions`.
+    Minimal,
+    /// The oldest for the dependencies of the root, and the newest for the rest,
+    /// like `cargo -Z direct-minimal-versions`.
+    DirectMinimal,
+}
 
 type IndexMapLookup = HashMap<
     InternedString,
@@ -53,15 +129,33 @@ type IndexMapLookup = HashMap<
     rustc_hash::FxBuildHasher,
 >;
 
+pub type Lock = HashMap<InternedString, BTreeSet<semver::Version>, rustc_hash::FxBuildHasher>;
+
 #[derive(Clone)]
 pub struct Index<'c> {
     crates: &'c IndexMapLookup,
-    past_result:
-        Option<HashMap<InternedString, BTreeSet<semver::Version>, rustc_hash::FxBuildHasher>>,
+    past_result: Option<Lock>,
     dependencies: RefCell<HashSet<(InternedString, semver::Version), rustc_hash::FxBuildHasher>>,
     pubgrub_dependencies: RefCell<HashSet<(Names<'c>, semver::Version), rustc_hash::FxBuildHasher>>,
+    /// `Budget::max_get_dependencies` counts the lookups of the current resolution only.
+    pubgrub_dependencies_at_start: usize,
+    cargo_queries: HashSet<(InternedString, String), rustc_hash::FxBuildHasher>,
     start: Cell<Instant>,
     should_cancel_call_count: Cell<u64>,
+    budget: Budget,
+    forbid_cycles: bool,
+    /// Indexed by the id of `Names::Cycle`.
+    cycles: Vec<Vec<(Names<'c>, semver::Version)>>,
+    roots: Vec<&'c index_data::Version>,
+    workspace: Vec<(Names<'c>, semver::Version)>,
+    root_features: RootFeatures,
+    root_dev_deps: bool,
+    target: Option<&'c Target>,
+    target_matches: RefCell<HashMap<InternedString, bool, rustc_hash::FxBuildHasher>>,
+    rust_version: Option<semver::Version>,
+    versions_order: VersionsOrder,
+    direct_deps: RefCell<HashSet<Names<'c>, rustc_hash::FxBuildHasher>>,
+    prefer_locked: Option<&'c Lock>,
 }
 
 impl<'c> Index<'c> {
@@ -71,19 +165,310 @@ impl<'c> Index<'c> {
             past_result: None,
             pubgrub_dependencies: Default::default(),
             dependencies: Default::default(),
+            pubgrub_dependencies_at_start: 0,
+            cargo_queries: Default::default(),
             start: Cell::new(Instant::now()),
             should_cancel_call_count: Cell::new(0),
+            budget: Budget::default(),
+            forbid_cycles: false,
+            cycles: Vec::new(),
+            roots: Vec::new(),
+            workspace: Vec::new(),
+            root_features: RootFeatures::All,
+            root_dev_deps: true,
+            target: None,
+            target_matches: Default::default(),
+            rust_version: None,
+            versions_order: VersionsOrder::Maximal,
+            direct_deps: Default::default(),
+            prefer_locked: None,
+        }
+    }
+
+    pub fn with_root(mut self, manifest: &'c index_data::Version) -> Self {
+        self.roots.push(manifest);
+        self
+    }
+
+    fn root_manifest(&self, name: InternedString) -> Option<&'c index_data::Version> {
+        self.roots.iter().find(|m| m.name == name).copied()
+    }
+
+    pub fn with_workspace(self, members: Vec<(Names<'c>, semver::Version)>) -> Self {
+        Self {
+            workspace: members,
+            ..self
+        }
+    }
+
+    fn root_version(&self, root: &Names, ver: &semver::Version) -> Option<&'c index_data::Version> {
+        match root {
+            Names::Root(name) => self.root_manifest(*name),
+            Names::Bucket(name, _, true) => self.get_version(name.as_str(), ver),
+            _ => None,
+        }
+    }
+
+    pub fn with_root_features(self, root_features: RootFeatures) -> Self {
+        Self {
+            root_features,
+            ..self
+        }
+    }
+
+    pub fn with_root_dev_deps(self, root_dev_deps: bool) -> Self {
+        Self {
+            root_dev_deps,
+            ..self
+        }
+    }
+
+    /// The cargo summaries in the index data need to be made with `summary_for_target` for the same `target`.
+    pub fn with_target(self, target: Option<&'c Target>) -> Self {
+        Self { target, ..self }
+    }
+
+    fn on_target(&self, dep: &index_data::Dependency) -> bool {
+        let (Some(target), Some(on)) = (self.target, dep.target) else {
+            return true;
+        };
+        *self
+            .target_matches
+            .borrow_mut()
+            .entry(on)
+            .or_insert_with(|| target.matches(&on))
+    }
+
+    pub fn with_rust_version(self, rust_version: Option<semver::Version>) -> Self {
+        Self {
+            rust_version,
+            ..self
+        }
+    }
+
+    pub fn with_versions_order(self, versions_order: VersionsOrder) -> Self {
+        Self {
+            versions_order,
+            ..self
+        }
+    }
+
+    /// Unlike `past_result` the other versions are still available.
+    pub fn with_prefer_locked(self, prefer_locked: Option<&'c Lock>) -> Self {
+        Self {
+            prefer_locked,
+            ..self
+        }
+    }
+
+    fn is_locked(&self, name: InternedString, ver: &semver::Version) -> bool {
+        self.prefer_locked
+            .and_then(|locked| locked.get(&name))
+            .map_or(false, |vers| vers.contains(ver))
+    }
+
+    fn minimal_first(&self, package: &Names<'c>) -> bool {
+        match self.versions_order {
+            VersionsOrder::Maximal => false,
+            VersionsOrder::Minimal => true,
+            VersionsOrder::DirectMinimal => {
+                let base = match package {
+                    Names::BucketFeatures(n, c, _) | Names::BucketDefaultFeatures(n, c) => {
+                        new_bucket(*n, c.clone(), false)
+                    }
+                    Names::WideFeatures(n, req, from, c, _)
+                    | Names::WideDefaultFeatures(n, req, from, c) => {
+                        new_wide(*n, req, *from, c.clone())
+                    }
+                    p => p.clone(),
+                };
+                self.direct_deps.borrow().contains(&base)
+            }
+        }
+    }
+
+    fn supports_rust_version(&self, name: InternedString, ver: &semver::Version) -> bool {
+        let Some(rust_version) = &self.rust_version else {
+            return true;
+        };
+        self.crates
+            .get(&name)
+            .and_then(|vers| vers.get(ver))
+            .and_then(|(index_ver, _)| index_ver.msrv())
+            .map_or(true, |msrv| &msrv <= rust_version)
+    }
+
+    pub fn config(&self) -> String {
+        let mut config = format!("--root-features {}", self.root_features);
+        if !self.root_dev_deps {
+            config += " --no-root-dev-deps";
+        }
+        if let Some(target) = self.target {
+            config += " --target ";
+            config += target.triple();
+        }
+        if let Some(rust_version) = &self.rust_version {
+            config += &format!(" --rust-version {rust_version}");
+        }
+        match self.versions_order {
+            VersionsOrder::Maximal => {}
+            VersionsOrder::Minimal => config += " --versions-order minimal",
+            VersionsOrder::DirectMinimal => config += " --versions-order direct-minimal",
+        }
+        if self.prefer_locked.is_some() {
+            config += " --prefer-locked";
+        }
+        config
+    }
+
+    /// Unlike the features of other crate versions these are not encoded as `Names::BucketFeatures`,
+    /// as those would be shared with the same crate version when it is also depended on by another crate.
+    fn root_activated(
+        &self,
+        index_ver: &index_data::Version,
+    ) -> Result<(BTreeSet<&'static str>, BTreeSet<&'static str>), String> {
+        let mut features: BTreeSet<&'static str> = match &self.root_features {
+            RootFeatures::All => index_ver.features.keys().map(|f| f.as_str()).collect(),
+            RootFeatures::Default => index_ver
+                .features
+                .keys()
+                .map(|f| f.as_str())
+                .filter(|f| *f == "default")
+                .collect(),
+            RootFeatures::NoDefault => BTreeSet::new(),
+            RootFeatures::List(list) => {
+                if let Some(missing) = list.iter().find(|f| !index_ver.features.contains_key(*f)) {
+                    return Err(format!("no feature `{missing}`"));
+                }
+                list.iter().map(|f| f.as_str()).collect()
+            }
+        };
+        let mut optional_deps = BTreeSet::new();
+        let mut todo = features.iter().copied().collect_vec();
+        while let Some(feat) = todo.pop() {
+            let Some(vals) = index_ver.features.get(feat) else {
+                continue;
+            };
+            for val in &**vals {
+                let activated = if let Some((dep, _)) = val.as_str().split_once('/') {
+                    if dep.ends_with('?') {
+                        // Weak, only adds features if the dependency is activated some other way.
+                        continue;
+                    }
+                    if !index_ver
+                        .deps
+                        .get(dep)
+                        .iter()
+                        .any(|d| d.optional && self.on_target(d))
+                    {
+                        continue;
+                    }
+                    optional_deps.insert(dep);
+                    // Also activates the feature of the same name, if there is one.
+                    FeatureNamespace::Feat(dep)
+                } else {
+                    FeatureNamespace::new(val.as_str())
+                };
+                match activated {
+                    FeatureNamespace::Dep(dep) => {
+                        optional_deps.insert(dep);
+                    }
+                    FeatureNamespace::Feat(f) => {
+                        if index_ver.features.contains_key(f) && features.insert(f) {
+                            todo.push(f);
+                        }
+                    }
+                }
+            }
+        }
+        Ok((features, optional_deps))
+    }
+
+    fn root_dependencies(
+        &self,
+        package: &Names<'c>,
+        index_ver: &'c index_data::Version,
+        version: &semver::Version,
+    ) -> Dependencies<Names<'c>, RcSemverPubgrub, String> {
+        let (features, optional_deps) = match self.root_activated(index_ver) {
+            Ok(activated) => activated,
+            Err(e) => return Dependencies::Unavailable(e),
+        };
+        let name = index_ver.name;
+        let mut deps = DependencyConstraints::default();
+        if let Some(link) = &index_ver.links {
+            deps.insert(new_links(*link), links_version(package, version));
+        }
+        for dep in index_ver.deps.iter() {
+            if dep.kind == DependencyKind::Dev && !self.root_dev_deps {
+                continue;
+            }
+            if !self.on_target(dep) {
+                continue;
+            }
+            if dep.optional && !optional_deps.contains(dep.name.as_str()) {
+                continue;
+            }
+            self.insert_dep(&mut deps, dep, name, version);
+        }
+        if self.versions_order == VersionsOrder::DirectMinimal {
+            self.direct_deps.borrow_mut().extend(
+                deps.keys()
+                    .filter(|p| matches!(p, Names::Bucket(..) | Names::Wide(..)))
+                    .cloned(),
+            );
+        }
+        for feat in features {
+            for val in &*index_ver.features[feat] {
+                let Some((dep, dep_feat)) = val.split_once('/') else {
+                    continue;
+                };
+                let dep_name = dep.strip_suffix('?').unwrap_or(dep);
+                for com in index_ver.deps.get(dep_name) {
+                    if com.kind == DependencyKind::Dev && !self.root_dev_deps {
+                        continue;
+                    }
+                    if !self.on_target(com) {
+                        continue;
+                    }
+                    if com.optional && !optional_deps.contains(dep_name) {
+                        continue;
+                    }
+                    let (cray, req_range) = self.from_dep(com, name, version);
+                    deps_insert(
+                        &mut deps,
+                        cray.with_features(FeatureNamespace::new(dep_feat)),
+                        req_range,
+                    );
+                }
+            }
+        }
+        Dependencies::Available(deps)
+    }
+
+    pub fn with_budget(self, budget: Budget) -> Self {
+        Self { budget, ..self }
+    }
+
+    pub fn with_forbid_cycles(self, forbid_cycles: bool) -> Self {
+        Self {
+            forbid_cycles,
+            ..self
         }
     }
 
     fn reset(&mut self) {
         self.past_result = None;
+        self.cycles.clear();
+        self.direct_deps.get_mut().clear();
         self.dependencies.get_mut().clear();
         self.pubgrub_dependencies.get_mut().clear();
         self.reset_time();
     }
 
     fn reset_time(&mut self) {
+        self.pubgrub_dependencies_at_start = self.pubgrub_dependencies.get_mut().len();
+        self.cargo_queries.clear();
         *self.should_cancel_call_count.get_mut() = 0;
         *self.start.get_mut() = Instant::now();
     }
@@ -96,6 +481,14 @@ impl<'c> Index<'c> {
         self.should_cancel_call_count.get()
     }
 
+    fn over_budget(&self, get_dependencies_call_count: usize) -> bool {
+        self.budget.time_cut_off < self.duration()
+            || self
+                .budget
+                .max_get_dependencies
+                .is_some_and(|max| max < get_dependencies_call_count)
+    }
+
     #[cfg(test)]
     fn make_pubgrub_ron_file(&self) {
         let mut dependency_provider: BTreeMap<_, BTreeMap<_, Result<_, _>>> = BTreeMap::new();
@@ -106,10 +499,7 @@ impl<'c> Index<'c> {
             .cloned()
             .collect_vec();
 
-        let Some(name) = deps
-            .iter()
-            .find(|(name, _)| matches!(name, Names::Bucket(_, _, all) if *all))
-        else {
+        let Some(name) = self.last_root() else {
             panic!("no root")
         };
 
@@ -142,31 +532,70 @@ impl<'c> Index<'c> {
         file.flush().unwrap();
     }
 
+    fn last_root(&self) -> Option<(Names<'c>, semver::Version)> {
+        self.pubgrub_dependencies
+            .borrow()
+            .iter()
+            .find(|(name, _)| {
+                matches!(
+                    name,
+                    Names::Bucket(_, _, true) | Names::Root(_) | Names::Workspace(_)
+                )
+            })
+            .cloned()
+    }
+
+    /// A `Names::Workspace` is written as a crate that depends on each member at its version,
+    /// which resolves the members like any other dependency rather than as roots.
+    /// So the members lose their dev-dependencies and `with_root_features`,
+    /// and a mismatch that depends on those can not be replayed or minimized from the file.
     fn make_index_ron_data(&self) -> Vec<index_data::Version> {
         let deps = self.dependencies.borrow();
 
         let name_vers: BTreeSet<_> = deps.iter().map(|(n, v)| (n.as_str(), v)).collect();
 
-        name_vers
-            .into_iter()
-            .map(|(n, version)| self.crates[n][version].0.clone())
-            .collect()
-    }
-
-    fn make_index_ron_file(&self) {
-        let grub_deps = self.pubgrub_dependencies.borrow();
-
-        let name = grub_deps
+        let mut data = name_vers
             .iter()
-            .find(|(name, _)| matches!(name, Names::Bucket(_, _, all) if *all))
-            .unwrap();
+            .map(|&(n, version)| self.crates[n][version].0.clone())
+            .collect_vec();
+        for manifest in &self.roots {
+            if !name_vers.contains(&(manifest.name.as_str(), &*manifest.vers)) {
+                data.push((*manifest).clone());
+            }
+        }
+        if let Some((Names::Workspace(name), ver)) = self.last_root() {
+            let members = self
+                .workspace
+                .iter()
+                .map(|(member, ver)| {
+                    index_data::Dependency::new(
+                        member.crate_(),
+                        member.crate_(),
+                        semver::VersionReq::parse(&format!("={ver}")).unwrap(),
+                        Vec::new(),
+                        true,
+                        DependencyKind::Normal,
+                        false,
+                    )
+                })
+                .collect();
+            data.push(index_data::Version::new(
+                name,
+                ver,
+                members,
+                BTreeMap::new(),
+                None,
+            ));
+        }
+        data
+    }
 
-        let out = self.make_index_ron_data();
+    fn make_index_ron_file(&self) -> String {
+        let name = self.last_root().unwrap();
 
         let file_name = format!("out/index_ron/{}@{}.ron", name.0.crate_(), name.1);
-        let mut file = BufWriter::new(File::create(&file_name).unwrap());
-        ron::ser::to_writer_pretty(&mut file, &out, PrettyConfig::new()).unwrap();
-        file.flush().unwrap();
+        write_index_ron_file(&file_name, &self.make_index_ron_data());
+        file_name
     }
 
     fn get_versions<Q>(&self, name: &Q) -> impl Iterator<Item = &semver::Version> + '_
@@ -174,23 +603,34 @@ impl<'c> Index<'c> {
         Q: ?Sized + Hash + Eq,
         InternedString: std::borrow::Borrow<Q>,
     {
-        if let Some(past) = self.past_result.as_ref() {
+        self.versions_in_order(name, self.versions_order == VersionsOrder::Minimal)
+    }
+
+    fn versions_in_order<Q>(
+        &self,
+        name: &Q,
+        minimal: bool,
+    ) -> impl Iterator<Item = &semver::Version> + '_
+    where
+        Q: ?Sized + Hash + Eq,
+        InternedString: std::borrow::Borrow<Q>,
+    {
+        let versions = if let Some(past) = self.past_result.as_ref() {
             let data = self.crates.get(name);
             Either::Left(
                 past.get(name)
                     .into_iter()
                     .flat_map(|m| m.iter())
-                    .rev()
                     .filter(move |v| data.map_or(false, |d| d.contains_key(v))),
             )
         } else {
-            Either::Right(
-                self.crates
-                    .get(name)
-                    .into_iter()
-                    .flat_map(|m| m.keys())
-                    .rev(),
-            )
+            Either::Right(self.crates.get(name).into_iter().flat_map(|m| m.keys()))
+        };
+        // The version tried first comes first, which also decides the bucket picked for `Names::Wide`.
+        if minimal {
+            Either::Left(versions)
+        } else {
+            Either::Right(versions.rev())
         }
     }
 
@@ -284,8 +724,63 @@ impl<'c> Index<'c> {
         }
     }
 
-    #[must_use]
-    fn check_cycles(&self, root: Names<'c>, pubmap: &SelectedDependencies<Self>) -> bool {
+    fn insert_dep(
+        &self,
+        deps: &mut DependencyConstraints<Names<'c>, RcSemverPubgrub>,
+        dep: &'c index_data::Dependency,
+        from: InternedString,
+        version: &semver::Version,
+    ) {
+        let (cray, req_range) = self.from_dep(dep, from, version);
+
+        deps_insert(deps, cray.clone(), req_range.clone());
+
+        if dep.default_features {
+            deps_insert(deps, cray.with_default_features(), req_range.clone());
+        }
+        for f in &*dep.features {
+            deps_insert(
+                deps,
+                cray.with_features(FeatureNamespace::new(f)),
+                req_range.clone(),
+            );
+        }
+    }
+
+    fn pub_features(
+        &self,
+        pubmap: &SelectedDependencies<Self>,
+    ) -> BTreeMap<(InternedString, semver::Version), BTreeSet<InternedString>> {
+        let mut out: BTreeMap<_, BTreeSet<_>> = BTreeMap::new();
+        for (name, ver) in pubmap {
+            match name {
+                Names::Bucket(_, _, true) | Names::Root(_) => {
+                    // The features of the root are not encoded as packages.
+                    let index_ver = self.root_version(name, ver).unwrap();
+                    let (features, _) = self.root_activated(index_ver).unwrap_or_default();
+                    out.entry((name.crate_(), ver.clone()))
+                        .or_default()
+                        .extend(features.into_iter().map(InternedString::new));
+                }
+                Names::Bucket(name, _, false) => {
+                    out.entry((*name, ver.clone())).or_default();
+                }
+                Names::BucketFeatures(name, _, FeatureNamespace::Feat(feat)) => {
+                    out.entry((*name, ver.clone()))
+                        .or_default()
+                        .insert(InternedString::new(feat));
+                }
+                _ => {}
+            }
+        }
+        out
+    }
+
+    fn check_cycles(
+        &self,
+        root: Names<'c>,
+        pubmap: &SelectedDependencies<Self>,
+    ) -> Result<(), CheckError> {
         let mut vertions: HashMap<
             (InternedString, SemverCompatibility, bool),
             (semver::Version, BTreeSet<_>, BTreeSet<_>),
@@ -294,7 +789,10 @@ impl<'c> Index<'c> {
         for (names, ver) in pubmap {
             if let Names::Bucket(name, cap, is_root) = names {
                 if cap != &SemverCompatibility::from(ver) {
-                    panic!("cap not meet");
+                    return Err(CheckError::IncompatibleVersion {
+                        package: names.to_string(),
+                        ver: ver.clone(),
+                    });
                 }
                 let old_val = vertions.insert(
                     (*name, *cap, *is_root),
@@ -302,43 +800,113 @@ impl<'c> Index<'c> {
                 );
 
                 if old_val.is_some() {
-                    panic!("duplicate package");
+                    return Err(CheckError::DuplicateBucket {
+                        name: *name,
+                        ver: ver.clone(),
+                    });
                 }
             }
         }
         // Identify the selected package features and deps
-        for (name, ver) in pubmap {
-            if let Names::BucketFeatures(name, cap, feat) = name {
+        for (names, ver) in pubmap {
+            if let Names::BucketFeatures(name, cap, feat) = names {
                 if cap != &SemverCompatibility::from(ver) {
-                    panic!("cap not meet for feature");
+                    return Err(CheckError::IncompatibleVersion {
+                        package: names.to_string(),
+                        ver: ver.clone(),
+                    });
                 }
-                let old_val = vertions.get_mut(&(*name, *cap, false)).unwrap();
+                let Some(old_val) = vertions.get_mut(&(*name, *cap, false)) else {
+                    return Err(CheckError::MissingBucket {
+                        package: names.to_string(),
+                        ver: ver.clone(),
+                    });
+                };
                 if &old_val.0 != ver {
-                    panic!("ver not match for feature");
+                    return Err(CheckError::VersionMismatch {
+                        package: names.to_string(),
+                        ver: ver.clone(),
+                        bucket_ver: old_val.0.clone(),
+                    });
                 }
                 let old_feat = match *feat {
                     FeatureNamespace::Feat(f) => old_val.1.insert(f),
                     FeatureNamespace::Dep(f) => old_val.2.insert(f),
                 };
                 if !old_feat {
-                    panic!("duplicate feature");
+                    return Err(CheckError::DuplicateFeature {
+                        package: names.to_string(),
+                        ver: ver.clone(),
+                    });
                 }
             }
         }
 
+        // The optional dependencies of the roots are activated by `root_features`, not by packages.
+        for ((name, _, is_root), (ver, _, deps)) in vertions.iter_mut() {
+            if *is_root {
+                let index_ver = self.get_version(name.as_str(), ver).ok_or_else(|| {
+                    CheckError::MissingVersion {
+                        name: *name,
+                        ver: ver.clone(),
+                    }
+                })?;
+                let (_, optional_deps) = self.root_activated(index_ver).unwrap_or_default();
+                deps.extend(optional_deps);
+            }
+        }
+
         let mut checked = HashSet::with_capacity(vertions.len());
         let mut visited = HashSet::with_capacity(4);
-        let Names::Bucket(name, cap, is_root) = root else {
-            panic!("root not bucket");
-        };
-        self.visit(
-            (name, cap, is_root),
-            pubmap,
-            &vertions,
-            &mut visited,
-            &mut checked,
-        )
-        .is_err()
+        let mut stack = Vec::with_capacity(4);
+        match root {
+            Names::Bucket(name, cap, is_root) => self.visit(
+                (name, cap, is_root),
+                pubmap,
+                &vertions,
+                &mut visited,
+                &mut stack,
+                &mut checked,
+            ),
+            Names::Root(name) => {
+                let manifest = self.root_manifest(name).ok_or(CheckError::MissingRoot)?;
+                let (_, optional_deps) = self.root_activated(manifest).unwrap_or_default();
+                for dep in manifest.deps.iter() {
+                    if dep.kind == DependencyKind::Dev || !self.on_target(dep) {
+                        continue;
+                    }
+                    if dep.optional && !optional_deps.contains(dep.name.as_str()) {
+                        continue;
+                    }
+                    let (cray, _) = self.from_dep(dep, name, &*manifest.vers);
+                    let dep_id = pubmap
+                        .get(&cray)
+                        .map(|dep_ver| (dep.package_name, dep_ver.into(), false))
+                        .filter(|dep_id| vertions.contains_key(dep_id));
+                    let Some(dep_id) = dep_id else {
+                        return Err(CheckError::MissingDependency {
+                            package: root.to_string(),
+                            ver: (*manifest.vers).clone(),
+                            dependency: cray.to_string(),
+                        });
+                    };
+                    self.visit(
+                        dep_id,
+                        pubmap,
+                        &vertions,
+                        &mut visited,
+                        &mut stack,
+                        &mut checked,
+                    )?;
+                }
+                Ok(())
+            }
+            Names::Workspace(_) => self
+                .workspace
+                .iter()
+                .try_for_each(|(member, _)| self.check_cycles(member.clone(), pubmap)),
+            _ => Err(CheckError::MissingRoot),
+        }
     }
 
     fn visit(
@@ -350,34 +918,64 @@ impl<'c> Index<'c> {
             (semver::Version, BTreeSet<&str>, BTreeSet<&str>),
         >,
         visited: &mut HashSet<(InternedString, SemverCompatibility, bool)>,
+        stack: &mut Vec<((InternedString, SemverCompatibility, bool), CycleStep)>,
         checked: &mut HashSet<(InternedString, SemverCompatibility, bool)>,
-    ) -> Result<(), ()> {
+    ) -> Result<(), CheckError> {
         if !visited.insert(id) {
             // We found a cycle and need to construct an error. Performance is no longer top priority.
-            return Err(());
+            // Everything in `visited` is on the stack, but if not the whole stack still ends in the cycle.
+            let start = stack.iter().position(|(s, _)| s == &id).unwrap_or(0);
+            return Err(CheckError::Cycle(
+                stack[start..]
+                    .iter()
+                    .map(|(_, step)| step.clone())
+                    .collect(),
+            ));
         }
 
         if checked.insert(id) {
-            let (version, _feats, deps) = &vertions[&id];
+            // The buckets of dependencies are checked before visiting them, so only the root can be missing.
+            let Some((version, _feats, deps)) = vertions.get(&id) else {
+                return Err(CheckError::MissingRoot);
+            };
 
-            let index_ver = self.get_version(id.0.as_str(), version).unwrap();
+            let index_ver = self.get_version(id.0.as_str(), version).ok_or_else(|| {
+                CheckError::MissingVersion {
+                    name: id.0,
+                    ver: version.clone(),
+                }
+            })?;
             for dep in index_ver.deps.iter() {
-                if dep.kind == DependencyKind::Dev {
+                if dep.kind == DependencyKind::Dev || !self.on_target(dep) {
                     continue;
                 }
-                if dep.optional && !id.2 && !deps.contains(dep.name.as_str()) {
+                if dep.optional && !deps.contains(dep.name.as_str()) {
                     continue;
                 }
                 let (cray, _) = self.from_dep(&dep, id.0, version);
 
-                let dep_ver = &pubmap[&cray];
-                self.visit(
-                    (dep.package_name, dep_ver.into(), false),
-                    pubmap,
-                    vertions,
-                    visited,
-                    checked,
-                )?;
+                let dep_id = pubmap
+                    .get(&cray)
+                    .map(|dep_ver| (dep.package_name, dep_ver.into(), false))
+                    .filter(|dep_id| vertions.contains_key(dep_id));
+                let Some(dep_id) = dep_id else {
+                    return Err(CheckError::MissingDependency {
+                        package: new_bucket(id.0, id.1, id.2).to_string(),
+                        ver: version.clone(),
+                        dependency: cray.to_string(),
+                    });
+                };
+                stack.push((
+                    id,
+                    CycleStep {
+                        name: id.0,
+                        ver: version.clone(),
+                        via: dep.name,
+                        kind: dep.kind,
+                    },
+                ));
+                self.visit(dep_id, pubmap, vertions, visited, stack, checked)?;
+                stack.pop();
             }
         }
 
@@ -385,26 +983,43 @@ impl<'c> Index<'c> {
         Ok(())
     }
 
-    #[must_use]
-    fn check(&self, root: Names, pubmap: &SelectedDependencies<Self>) -> bool {
+    /// Each member `i` of `n` depends on a `Names::Cycle` in `0..n` but not `i`,
+    /// so when all of them are selected there is no version of the `Names::Cycle` left.
+    fn forbid_cycle(&mut self, cycle: &[CycleStep]) -> Result<(), CheckError> {
+        let members = cycle
+            .iter()
+            .map(|step| {
+                let compat = SemverCompatibility::from(&step.ver);
+                let index_ver =
+                    self.get_version(step.name.as_str(), &step.ver)
+                        .ok_or_else(|| CheckError::MissingVersion {
+                            name: step.name,
+                            ver: step.ver.clone(),
+                        })?;
+                let optional = index_ver
+                    .deps
+                    .get(step.via)
+                    .iter()
+                    .filter(|dep| dep.kind != DependencyKind::Dev)
+                    .all(|dep| dep.optional);
+                let name = if optional {
+                    new_bucket(step.name, compat, false)
+                        .with_features(FeatureNamespace::Dep(step.via.as_str()))
+                } else {
+                    new_bucket(step.name, compat, false)
+                };
+                Ok((name, step.ver.clone()))
+            })
+            .collect::<Result<_, _>>()?;
+        self.cycles.push(members);
+        Ok(())
+    }
+
+    fn check(&self, root: Names, pubmap: &SelectedDependencies<Self>) -> Result<(), CheckError> {
         // Basic dependency resolution properties
         if !pubmap.contains_key(&root) {
-            return false;
-        }
-        for (name, ver) in pubmap {
-            let Dependencies::Available(deps) = self.get_dependencies(name, ver).unwrap() else {
-                return false;
-            };
-            for (dep, req) in deps {
-                let Some(dep_ver) = pubmap.get(&dep) else {
-                    return false;
-                };
-                if !req.contains(dep_ver) {
-                    return false;
-                }
-            }
+            return Err(CheckError::MissingRoot);
         }
-
         let mut vertions: HashMap<
             (InternedString, SemverCompatibility),
             (semver::Version, BTreeSet<_>, BTreeSet<_>, bool),
@@ -413,7 +1028,10 @@ impl<'c> Index<'c> {
         for (names, ver) in pubmap {
             if let Names::Bucket(name, cap, is_root) = names {
                 if cap != &SemverCompatibility::from(ver) {
-                    return false;
+                    return Err(CheckError::IncompatibleVersion {
+                        package: names.to_string(),
+                        ver: ver.clone(),
+                    });
                 }
                 if *is_root {
                     continue;
@@ -424,40 +1042,73 @@ impl<'c> Index<'c> {
                 );
 
                 if old_val.is_some() {
-                    return false;
+                    return Err(CheckError::DuplicateBucket {
+                        name: *name,
+                        ver: ver.clone(),
+                    });
                 }
             }
         }
         // Identify the selected package features and deps
-        for (name, ver) in pubmap {
-            if let Names::BucketFeatures(name, cap, feat) = name {
+        for (names, ver) in pubmap {
+            if let Names::BucketFeatures(name, cap, feat) = names {
                 if cap != &SemverCompatibility::from(ver) {
-                    return false;
+                    return Err(CheckError::IncompatibleVersion {
+                        package: names.to_string(),
+                        ver: ver.clone(),
+                    });
                 }
-                let old_val = vertions.get_mut(&(*name, *cap)).unwrap();
+                let Some(old_val) = vertions.get_mut(&(*name, *cap)) else {
+                    return Err(CheckError::MissingBucket {
+                        package: names.to_string(),
+                        ver: ver.clone(),
+                    });
+                };
                 if &old_val.0 != ver {
-                    return false;
+                    return Err(CheckError::VersionMismatch {
+                        package: names.to_string(),
+                        ver: ver.clone(),
+                        bucket_ver: old_val.0.clone(),
+                    });
                 }
                 let old_feat = match *feat {
                     FeatureNamespace::Feat(f) => old_val.1.insert(f),
                     FeatureNamespace::Dep(f) => old_val.2.insert(f),
                 };
                 if !old_feat {
-                    return false;
+                    return Err(CheckError::DuplicateFeature {
+                        package: names.to_string(),
+                        ver: ver.clone(),
+                    });
                 }
             }
         }
-        for (name, ver) in pubmap {
-            if let Names::BucketDefaultFeatures(name, cap) = name {
+        for (names, ver) in pubmap {
+            if let Names::BucketDefaultFeatures(name, cap) = names {
                 if cap != &SemverCompatibility::from(ver) {
-                    return false;
+                    return Err(CheckError::IncompatibleVersion {
+                        package: names.to_string(),
+                        ver: ver.clone(),
+                    });
                 }
-                let old_val = vertions.get_mut(&(*name, *cap)).unwrap();
+                let Some(old_val) = vertions.get_mut(&(*name, *cap)) else {
+                    return Err(CheckError::MissingBucket {
+                        package: names.to_string(),
+                        ver: ver.clone(),
+                    });
+                };
                 if &old_val.0 != ver {
-                    return false;
+                    return Err(CheckError::VersionMismatch {
+                        package: names.to_string(),
+                        ver: ver.clone(),
+                        bucket_ver: old_val.0.clone(),
+                    });
                 }
                 if old_val.3 {
-                    return false;
+                    return Err(CheckError::DuplicateFeature {
+                        package: names.to_string(),
+                        ver: ver.clone(),
+                    });
                 }
                 old_val.3 = true;
             }
@@ -465,24 +1116,42 @@ impl<'c> Index<'c> {
 
         let mut links: BTreeSet<_> = BTreeSet::new();
         for ((name, _), (ver, feats, deps, default_feature)) in vertions.iter() {
-            let index_ver = self.get_version(name.as_str(), ver).unwrap();
+            let index_ver =
+                self.get_version(name.as_str(), ver)
+                    .ok_or_else(|| CheckError::MissingVersion {
+                        name: *name,
+                        ver: ver.clone(),
+                    })?;
             if index_ver.yanked {
-                return false;
+                return Err(CheckError::YankedSelected {
+                    name: *name,
+                    ver: ver.clone(),
+                });
             }
             if let Some(link) = &index_ver.links {
                 let old_link = links.insert(link.clone());
                 if !old_link {
-                    return false;
+                    return Err(CheckError::DuplicateLinks {
+                        links: *link,
+                        name: *name,
+                        ver: ver.clone(),
+                    });
                 }
             }
 
             if *default_feature {
                 if index_ver.features.contains_key("default") != feats.contains("default") {
-                    return false;
+                    return Err(CheckError::DefaultFeatureMismatch {
+                        name: *name,
+                        ver: ver.clone(),
+                    });
                 }
             }
 
             for dep in index_ver.deps.iter() {
+                if !self.on_target(dep) {
+                    continue;
+                }
                 if dep.optional && !deps.contains(&*dep.name) {
                     continue;
                 }
@@ -509,16 +1178,273 @@ impl<'c> Index<'c> {
                     },
                 );
                 if fulfilled.is_none() {
-                    return false;
+                    return Err(CheckError::UnsatisfiedDependency {
+                        package: name.to_string(),
+                        ver: ver.clone(),
+                        dependency: format!("{} {}", dep.package_name, dep.req),
+                        selected: None,
+                    });
+                }
+            }
+
+            for feat in feats.iter() {
+                let Some(items) = index_ver.features.get(*feat) else {
+                    return Err(CheckError::UnsatisfiedFeature {
+                        name: *name,
+                        ver: ver.clone(),
+                        feature: InternedString::new(feat),
+                        item: InternedString::new(feat),
+                    });
+                };
+                for item in items.iter() {
+                    let satisfied = if let Some(dep_name) = item.strip_prefix("dep:") {
+                        deps.contains(dep_name)
+                    } else if let Some((dep_name, dep_feat)) = item.split_once('/') {
+                        let weak = dep_name.ends_with('?');
+                        let dep_name = dep_name.strip_suffix('?').unwrap_or(dep_name);
+                        index_ver
+                            .deps
+                            .get(dep_name)
+                            .iter()
+                            .filter(|dep| dep.kind != DependencyKind::Dev && self.on_target(dep))
+                            .all(|dep| {
+                                if dep.optional && !deps.contains(dep_name) {
+                                    // A weak feature does not activate the dependency.
+                                    return weak;
+                                }
+                                vertions.iter().any(
+                                    |((other_name, _), (other_ver, other_feats, _, _))| {
+                                        **other_name == *dep.package_name
+                                            && dep.req.matches(other_ver)
+                                            && other_feats.contains(dep_feat)
+                                    },
+                                )
+                            })
+                    } else {
+                        feats.contains(item.as_str())
+                    };
+                    if !satisfied {
+                        return Err(CheckError::UnsatisfiedFeature {
+                            name: *name,
+                            ver: ver.clone(),
+                            feature: InternedString::new(feat),
+                            item: *item,
+                        });
+                    }
+                }
+            }
+        }
+        // The dependencies of the encoding, which describe a problem less clearly than the checks above.
+        for (name, ver) in pubmap {
+            let Ok(deps) = self.get_dependencies(name, ver) else {
+                return Err(CheckError::MissingVersion {
+                    name: name.crate_(),
+                    ver: ver.clone(),
+                });
+            };
+            let Dependencies::Available(deps) = deps else {
+                return Err(CheckError::UnavailableDependencies {
+                    package: name.to_string(),
+                    ver: ver.clone(),
+                });
+            };
+            for (dep, req) in deps {
+                let dep_ver = pubmap.get(&dep);
+                if !dep_ver.is_some_and(|dep_ver| req.contains(dep_ver)) {
+                    return Err(CheckError::UnsatisfiedDependency {
+                        package: name.to_string(),
+                        ver: ver.clone(),
+                        dependency: format!("{dep} {req}"),
+                        selected: dep_ver.cloned(),
+                    });
                 }
             }
+        }
+        Ok(())
+    }
+}
+
+/// Packages are recorded as the display of their `Names`, as the `Names` borrow from the index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CheckError {
+    MissingRoot,
+    UnavailableDependencies {
+        package: String,
+        ver: semver::Version,
+    },
+    MissingDependency {
+        package: String,
+        ver: semver::Version,
+        dependency: String,
+    },
+    MissingBucket {
+        package: String,
+        ver: semver::Version,
+    },
+    MissingVersion {
+        name: InternedString,
+        ver: semver::Version,
+    },
+    UnsatisfiedDependency {
+        package: String,
+        ver: semver::Version,
+        dependency: String,
+        selected: Option<semver::Version>,
+    },
+    IncompatibleVersion {
+        package: String,
+        ver: semver::Version,
+    },
+    VersionMismatch {
+        package: String,
+        ver: semver::Version,
+        bucket_ver: semver::Version,
+    },
+    DuplicateBucket {
+        name: InternedString,
+        ver: semver::Version,
+    },
+    DuplicateFeature {
+        package: String,
+        ver: semver::Version,
+    },
+    DuplicateLinks {
+        links: InternedString,
+        name: InternedString,
+        ver: semver::Version,
+    },
+    YankedSelected {
+        name: InternedString,
+        ver: semver::Version,
+    },
+    DefaultFeatureMismatch {
+        name: InternedString,
+        ver: semver::Version,
+    },
+    UnsatisfiedFeature {
+        name: InternedString,
+        ver: semver::Version,
+        feature: InternedString,
+        item: InternedString,
+    },
+    /// The last step depends on the crate of the first.
+    Cycle(Vec<CycleStep>),
+}
+
+#[derive(serde::Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct CycleStep {
+    pub name: InternedString,
+    pub ver: semver::Version,
+    /// The name of the dependency in the manifest, which differs from the next crate if renamed.
+    pub via: InternedString,
+    pub kind: DependencyKind,
+}
+
+impl std::fmt::Display for CycleStep {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}@{} -({} {:?})->",
+            self.name, self.ver, self.via, self.kind
+        )
+    }
+}
 
-            // todo: check index_ver.features
+impl std::fmt::Display for CheckError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CheckError::UnsatisfiedFeature {
+                name,
+                ver,
+                feature,
+                item,
+            } if feature == item => write!(f, "{name}@{ver} has no feature `{feature}`"),
+            CheckError::UnsatisfiedFeature {
+                name,
+                ver,
+                feature,
+                item,
+            } => write!(
+                f,
+                "feature `{feature}` of {name}@{ver} requires `{item}`, which is not satisfied"
+            ),
+            CheckError::MissingRoot => f.write_str("the root is not selected"),
+            CheckError::UnavailableDependencies { package, ver } => {
+                write!(f, "{package}@{ver} is selected but unavailable")
+            }
+            CheckError::MissingDependency {
+                package,
+                ver,
+                dependency,
+            } => write!(
+                f,
+                "{package}@{ver} depends on {dependency}, which is not selected"
+            ),
+            CheckError::MissingBucket { package, ver } => {
+                write!(f, "{package}@{ver} is selected without its bucket")
+            }
+            CheckError::MissingVersion { name, ver } => {
+                write!(f, "{name}@{ver} is selected but not in the index")
+            }
+            CheckError::UnsatisfiedDependency {
+                package,
+                ver,
+                dependency,
+                selected: Some(selected),
+            } => write!(
+                f,
+                "{package}@{ver} depends on {dependency}, but {selected} is selected"
+            ),
+            CheckError::UnsatisfiedDependency {
+                package,
+                ver,
+                dependency,
+                selected: None,
+            } => write!(
+                f,
+                "{package}@{ver} depends on {dependency}, which is not selected"
+            ),
+            CheckError::IncompatibleVersion { package, ver } => {
+                write!(f, "{package} is selected at incompatible version {ver}")
+            }
+            CheckError::VersionMismatch {
+                package,
+                ver,
+                bucket_ver,
+            } => write!(
+                f,
+                "{package} is selected at {ver}, but its bucket at {bucket_ver}"
+            ),
+            CheckError::DuplicateBucket { name, ver } => {
+                write!(f, "{name}@{ver} is selected twice")
+            }
+            CheckError::DuplicateFeature { package, ver } => {
+                write!(f, "{package}@{ver} is selected twice")
+            }
+            CheckError::DuplicateLinks { links, name, ver } => write!(
+                f,
+                "{name}@{ver} links to `{links}`, which another selected crate also links to"
+            ),
+            CheckError::YankedSelected { name, ver } => {
+                write!(f, "{name}@{ver} is yanked but selected")
+            }
+            CheckError::DefaultFeatureMismatch { name, ver } => write!(
+                f,
+                "the default features of {name}@{ver} do not match the `default` feature"
+            ),
+            CheckError::Cycle(path) => {
+                write!(f, "cyclic package dependency: {}", path.iter().join(" "))?;
+                if let Some(first) = path.first() {
+                    write!(f, " {}", first.name)?;
+                }
+                Ok(())
+            }
         }
-        true
     }
 }
 
+impl Error for CheckError {}
+
 #[derive(Debug)]
 pub struct SomeError;
 
@@ -530,6 +1456,12 @@ impl std::fmt::Display for SomeError {
 
 impl Error for SomeError {}
 
+pub fn write_index_ron_file(file_name: &str, data: &[index_data::Version]) {
+    let mut file = BufWriter::new(File::create(file_name).unwrap());
+    ron::ser::to_writer_pretty(&mut file, &data, PrettyConfig::new()).unwrap();
+    file.flush().unwrap();
+}
+
 fn deps_insert<'c>(
     deps: &mut DependencyConstraints<Names<'c>, RcSemverPubgrub>,
     n: Names<'c>,
@@ -540,6 +1472,26 @@ fn deps_insert<'c>(
         .or_insert(r);
 }
 
+/// The version of `Names::Links` selected by `package@version`, unique to each crate version.
+fn links_version(package: &Names, version: &semver::Version) -> RcSemverPubgrub {
+    let index_unique_to_each_crate_version = {
+        let mut state = StableHasher::new();
+        package.hash(&mut state);
+        version.hash(&mut state);
+        state.finish()
+    };
+    let ver = semver::Version::new(index_unique_to_each_crate_version, 0, 0);
+    RcSemverPubgrub::singleton(ver)
+}
+
+/// The versions of a `Names::Cycle` with `len` members that member `i` allows.
+fn cycle_range(len: usize, i: usize) -> RcSemverPubgrub {
+    (0..len as u64)
+        .filter(|&j| j != i as u64)
+        .map(|j| RcSemverPubgrub::singleton(semver::Version::new(j, 0, 0)))
+        .fold(RcSemverPubgrub::empty(), |acc, v| acc.union(&v))
+}
+
 impl<'c> DependencyProvider for Index<'c> {
     type P = Names<'c>;
 
@@ -551,7 +1503,7 @@ impl<'c> DependencyProvider for Index<'c> {
     type Err = SomeError;
     fn choose_version(
         &self,
-        package: &Names,
+        package: &Names<'c>,
         range: &RcSemverPubgrub,
     ) -> Result<Option<semver::Version>, Self::Err> {
         Ok(match package {
@@ -561,23 +1513,52 @@ impl<'c> DependencyProvider for Index<'c> {
                 };
                 Some(v.clone())
             }
+            Names::Root(name) => self
+                .root_manifest(*name)
+                .map(|m| (*m.vers).clone())
+                .filter(|v| range.contains(v)),
+            Names::Workspace(_) => {
+                Some(semver::Version::new(0, 0, 0)).filter(|v| range.contains(v))
+            }
+            Names::Cycle(_, id) => (0..self.cycles[*id].len() as u64)
+                .map(|i| semver::Version::new(i, 0, 0))
+                .find(|v| range.contains(v)),
 
             Names::Wide(_, req, _, _)
             | Names::WideFeatures(_, req, _, _, _)
             | Names::WideDefaultFeatures(_, req, _, _) => {
+                let name = package.crate_();
+                let minimal = self.minimal_first(package);
                 // one version for each bucket that match req
-                self.get_versions(&*package.crate_())
-                    .filter(|v| req.matches(v))
-                    .map(|v| SemverCompatibility::from(v))
-                    .map(|v| v.canonical())
-                    .find(|v| range.contains(v))
+                let canonical = |preferred: &dyn Fn(&semver::Version) -> bool| {
+                    self.versions_in_order(&*name, minimal)
+                        .filter(|v| req.matches(v))
+                        .filter(|v| preferred(v))
+                        .map(|v| SemverCompatibility::from(v))
+                        .map(|v| v.canonical())
+                        .find(|v| range.contains(v))
+                };
+                canonical(&|v| self.is_locked(name, v))
+                    .or_else(|| canonical(&|v| self.supports_rust_version(name, v)))
+                    .or_else(|| canonical(&|_| true))
             }
             Names::Bucket(_, _, _)
             | Names::BucketFeatures(_, _, _)
-            | Names::BucketDefaultFeatures(_, _) => self
-                .get_versions(&*package.crate_())
-                .find(|v| range.contains(v))
-                .cloned(),
+            | Names::BucketDefaultFeatures(_, _) => {
+                let name = package.crate_();
+                let minimal = self.minimal_first(package);
+                let matching = || {
+                    self.versions_in_order(&*name, minimal)
+                        .filter(|v| range.contains(v))
+                };
+                // Prefer the locked versions, then the versions that support `rust_version`,
+                // but fall back to those that do not.
+                matching()
+                    .find(|v| self.is_locked(name, v))
+                    .or_else(|| matching().find(|v| self.supports_rust_version(name, v)))
+                    .or_else(|| matching().next())
+                    .cloned()
+            }
         })
     }
 
@@ -594,6 +1575,9 @@ impl<'c> DependencyProvider for Index<'c> {
                 // So only rubberstamp links attributes when all other decisions are made, by setting the priority as low as it will go.
                 usize::MAX
             }
+            // Same as links, the members of the cycle are what needs deciding.
+            Names::Cycle(_, _) => usize::MAX,
+            Names::Root(_) | Names::Workspace(_) => 1,
 
             Names::Wide(_, req, _, _) => self.count_wide_matches(range, &package.crate_(), req),
             Names::WideFeatures(_, req, _, _, _) | Names::WideDefaultFeatures(_, req, _, _) => self
@@ -615,8 +1599,20 @@ impl<'c> DependencyProvider for Index<'c> {
         self.pubgrub_dependencies
             .borrow_mut()
             .insert((package.clone(), version.clone()));
-        Ok(match package {
-            &Names::Bucket(name, _major, all_features) => {
+        let mut dependencies = match package {
+            &Names::Root(name) => {
+                let Some(manifest) = self.root_manifest(name) else {
+                    return Err(SomeError);
+                };
+                self.root_dependencies(package, manifest, version)
+            }
+            Names::Workspace(_) => Dependencies::Available(
+                self.workspace
+                    .iter()
+                    .map(|(member, ver)| (member.clone(), RcSemverPubgrub::singleton(ver.clone())))
+                    .collect(),
+            ),
+            &Names::Bucket(name, _major, is_root) => {
                 let Some(index_ver) = self.get_version(name.as_str(), version) else {
                     return Err(SomeError);
                 };
@@ -626,58 +1622,25 @@ impl<'c> DependencyProvider for Index<'c> {
                 if index_ver.yanked {
                     return Ok(Dependencies::Unavailable("yanked: Bucket".into()));
                 }
-                let mut deps = DependencyConstraints::default();
-                if let Some(link) = &index_ver.links {
-                    let index_unique_to_each_crate_version = {
-                        let mut state = StableHasher::new();
-                        package.hash(&mut state);
-                        version.hash(&mut state);
-                        state.finish()
-                    };
-                    let ver = semver::Version::new(index_unique_to_each_crate_version, 0, 0);
-                    deps.insert(new_links(*link), RcSemverPubgrub::singleton(ver));
-                }
-                for dep in index_ver.deps.iter() {
-                    if dep.kind == DependencyKind::Dev && !all_features {
-                        continue;
-                    }
-                    if dep.optional && !all_features {
-                        continue; // handled in Names::Features
-                    }
-
-                    let (cray, req_range) = self.from_dep(&dep, name, version);
-
-                    deps_insert(&mut deps, cray.clone(), req_range.clone());
-
-                    if dep.default_features {
-                        deps_insert(&mut deps, cray.with_default_features(), req_range.clone());
-                    }
-                    for f in &*dep.features {
-                        deps_insert(
-                            &mut deps,
-                            cray.with_features(FeatureNamespace::new(f)),
-                            req_range.clone(),
-                        );
+                if is_root {
+                    self.root_dependencies(package, index_ver, version)
+                } else {
+                    let mut deps = DependencyConstraints::default();
+                    if let Some(link) = &index_ver.links {
+                        deps.insert(new_links(*link), links_version(package, version));
                     }
-                }
-                if all_features {
-                    for vals in index_ver.features.values() {
-                        for val in &**vals {
-                            if let Some((dep, dep_feat)) = val.split_once('/') {
-                                let dep_name = dep.strip_suffix('?').unwrap_or(dep);
-                                for com in index_ver.deps.get(dep_name) {
-                                    let (cray, req_range) = self.from_dep(com, name, version);
-                                    deps_insert(
-                                        &mut deps,
-                                        cray.with_features(FeatureNamespace::new(dep_feat)),
-                                        req_range,
-                                    );
-                                }
-                            }
+                    for dep in index_ver.deps.iter() {
+                        if dep.kind == DependencyKind::Dev || !self.on_target(dep) {
+                            continue;
                         }
+                        if dep.optional {
+                            continue; // handled in Names::Features
+                        }
+
+                        self.insert_dep(&mut deps, dep, name, version);
                     }
+                    Dependencies::Available(deps)
                 }
-                Dependencies::Available(deps)
             }
             Names::BucketFeatures(name, _major, FeatureNamespace::Feat(feat)) => {
                 let Some(index_ver) = self.get_version(name.as_str(), version) else {
@@ -705,7 +1668,7 @@ impl<'c> DependencyProvider for Index<'c> {
                             let dep_name = dep_name.unwrap_or(dep);
 
                             for dep in index_ver.deps.get(dep_name) {
-                                if dep.kind == DependencyKind::Dev {
+                                if dep.kind == DependencyKind::Dev || !self.on_target(dep) {
                                     continue;
                                 }
                                 let (cray, req_range) = self.from_dep(dep, *name, version);
@@ -799,20 +1762,11 @@ impl<'c> DependencyProvider for Index<'c> {
                         continue;
                     }
                     found_name = true;
-                    let (cray, req_range) = self.from_dep(&dep, *name, version);
-
-                    deps_insert(&mut deps, cray.clone(), req_range.clone());
-
-                    if dep.default_features {
-                        deps_insert(&mut deps, cray.with_default_features(), req_range.clone());
-                    }
-                    for f in &*dep.features {
-                        deps_insert(
-                            &mut deps,
-                            cray.with_features(FeatureNamespace::new(f)),
-                            req_range.clone(),
-                        );
+                    // Activating a dependency of another platform does nothing.
+                    if !self.on_target(dep) {
+                        continue;
                     }
+                    self.insert_dep(&mut deps, dep, *name, version);
                 }
 
                 if found_name {
@@ -827,11 +1781,13 @@ impl<'c> DependencyProvider for Index<'c> {
                 let req_range = SemverPubgrub::from(*req);
                 let range = req_range.intersection(&compat_range);
                 let range = RcSemverPubgrub::new(range.clone());
+                let bucket = new_bucket(*name, compatibility, false);
+                if self.direct_deps.borrow().contains(package) {
+                    // The bucket picked for a dependency of the root is one too.
+                    self.direct_deps.borrow_mut().insert(bucket.clone());
+                }
 
-                Dependencies::Available(DependencyConstraints::from_iter([(
-                    new_bucket(*name, compatibility, false),
-                    range,
-                )]))
+                Dependencies::Available(DependencyConstraints::from_iter([(bucket, range)]))
             }
             Names::WideFeatures(name, req, parent, parent_com, feat) => {
                 let compatibility = SemverCompatibility::from(version);
@@ -868,20 +1824,44 @@ impl<'c> DependencyProvider for Index<'c> {
                     ),
                 ]))
             }
-            Names::Links(_) => Dependencies::Available(DependencyConstraints::default()),
-        })
+            Names::Links(_) | Names::Cycle(_, _) => {
+                Dependencies::Available(DependencyConstraints::default())
+            }
+        };
+        if let Dependencies::Available(deps) = &mut dependencies {
+            for (id, members) in self.cycles.iter().enumerate() {
+                for (i, (name, ver)) in members.iter().enumerate() {
+                    if name == package && ver == version {
+                        deps_insert(
+                            deps,
+                            Names::Cycle(members[0].0.crate_(), id),
+                            cycle_range(members.len(), i),
+                        );
+                    }
+                }
+            }
+        }
+        Ok(dependencies)
     }
 
     fn should_cancel(&self) -> Result<(), Self::Err> {
         let calls = self.should_cancel_call_count.get();
         self.should_cancel_call_count.set(calls + 1);
-        if calls % 64 == 0 && TIME_CUT_OFF < self.start.get().elapsed().as_secs_f32() {
+        let lookups = self.pubgrub_dependencies.borrow().len() - self.pubgrub_dependencies_at_start;
+        if calls % 64 == 0 && self.over_budget(lookups) {
             return Err(SomeError);
         }
         Ok(())
     }
 }
 
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Compare {
+    pub solutions: bool,
+    pub dump_solution_diffs: bool,
+    pub features: bool,
+}
+
 #[derive(clap::ValueEnum, Clone, Debug, Copy)]
 pub enum Mode {
     All,
@@ -938,8 +1918,76 @@ pub fn process_crate_version(
     crt: InternedString,
     ver: semver::Version,
     mode: Mode,
+    compare: Compare,
 ) -> OutputSummary {
     let root = new_bucket(crt, (&ver).into(), true);
+    process_root(dp, root, ver, mode, compare)
+}
+
+struct CheckedResolution<'c> {
+    res: PubResult<'c>,
+    /// The seconds it took, without the checks.
+    time: f32,
+    forbidden_cycles: Vec<Vec<CycleStep>>,
+    cycle: Option<Vec<CycleStep>>,
+    check_error: Option<CheckError>,
+}
+
+/// After this many, the next cycle is reported like without `Index::with_forbid_cycles`.
+const MAX_FORBIDDEN_CYCLES: usize = 16;
+
+/// The rounds of `Index::with_forbid_cycles` share one budget, as `Index::reset` is not called between them.
+fn resolve_and_check<'c>(
+    dp: &mut Index<'c>,
+    root: Names<'c>,
+    ver: &semver::Version,
+) -> CheckedResolution<'c> {
+    let mut res = resolve(dp, root.clone(), ver.clone());
+    let mut forbidden_cycles = vec![];
+    let mut check_error = None;
+    while let Ok(map) = &res {
+        if !dp.forbid_cycles || forbidden_cycles.len() >= MAX_FORBIDDEN_CYCLES {
+            break;
+        }
+        let Err(CheckError::Cycle(cycle)) = dp.check_cycles(root.clone(), map) else {
+            break;
+        };
+        if let Err(e) = dp.forbid_cycle(&cycle) {
+            check_error = Some(e);
+            break;
+        }
+        forbidden_cycles.push(cycle);
+        res = resolve(dp, root.clone(), ver.clone());
+    }
+    let time = dp.duration();
+    let mut cycle = None;
+    if let Ok(map) = &res {
+        let checked = match dp.check_cycles(root.clone(), map) {
+            Err(CheckError::Cycle(c)) => {
+                cycle = Some(c);
+                Ok(())
+            }
+            cycles => cycles.and_then(|()| dp.check(root.clone(), map)),
+        };
+        check_error = check_error.or(checked.err());
+    }
+    CheckedResolution {
+        res,
+        time,
+        forbidden_cycles,
+        cycle,
+        check_error,
+    }
+}
+
+pub fn process_root<'c>(
+    dp: &mut Index<'c>,
+    root: Names<'c>,
+    ver: semver::Version,
+    mode: Mode,
+    compare: Compare,
+) -> OutputSummary {
+    let crt = root.crate_();
     dp.reset();
     let mut pub_cyclic_package_dependency = None;
     let mut cyclic_package_dependency = false;
@@ -947,82 +1995,193 @@ pub fn process_crate_version(
     let mut pub_time = 0.0;
     let mut should_cancel_call_count = 0;
     let mut get_dependencies_call_count = 0;
+    let mut mismatches = vec![];
+    let mut pub_cycle = None;
+    let mut forbidden_cycles = vec![];
     if mode.build_pub() {
-        res = Some(resolve(dp, root.clone(), (&ver).clone()));
-        cyclic_package_dependency = if let Some(Ok(map)) = res.as_ref() {
-            dp.check_cycles(root.clone(), map)
-        } else {
-            false
-        };
-        pub_cyclic_package_dependency = Some(cyclic_package_dependency);
-        pub_time = dp.duration();
+        let checked = resolve_and_check(dp, root.clone(), &ver);
+        pub_time = checked.time;
         should_cancel_call_count = dp.should_cancel_call_count();
         get_dependencies_call_count = dp.pubgrub_dependencies.borrow().len();
-        match res.as_ref().unwrap().as_ref() {
-            Ok(map) => {
-                if !dp.check(root.clone(), &map) {
-                    dp.make_index_ron_file();
-                    panic!("failed check");
-                }
-            }
-            Err(PubGrubError::NoSolution(_derivation)) => {}
-            Err(e) => {
+        forbidden_cycles = checked.forbidden_cycles;
+        pub_cycle = checked.cycle;
+        if let Some(e) = checked.check_error {
+            let fixture = dp.make_index_ron_file();
+            println!("failed check {root:?}: {e}");
+            mismatches.push(Mismatch {
+                check_error: Some(e.to_string()),
+                ..Mismatch::new(
+                    crt,
+                    &ver,
+                    MismatchKind::InvalidSolution,
+                    None,
+                    None,
+                    fixture,
+                )
+            });
+        }
+        match &checked.res {
+            Ok(_) | Err(PubGrubError::NoSolution(_)) => {}
+            Err(_) => {
                 dp.make_index_ron_file();
-                dbg!(e);
             }
         }
-        if pub_time > TIME_MAKE_FILE {
+        res = Some(checked.res);
+        if pub_time > dp.budget.time_make_file {
             dp.make_index_ron_file();
         }
+        cyclic_package_dependency = pub_cycle.is_some();
+        pub_cyclic_package_dependency = Some(cyclic_package_dependency);
     }
     let mut cargo_out = None;
     let mut cargo_time = 0.0;
     if mode.build_cargo() {
         dp.reset_time();
-        cargo_out = Some(cargo_resolver::resolve(crt, &ver, dp));
+        cargo_out = Some(cargo_resolver::resolve(&root, &ver, dp));
         cargo_time = dp.duration();
+        if cargo_time > dp.budget.time_make_file {
+            dp.make_index_ron_file();
+        }
         cyclic_package_dependency = &cargo_out
             .as_ref()
             .unwrap()
             .as_ref()
             .map_err(|e| e.to_string().starts_with("cyclic package dependency"))
             == &Err(true);
-        if let Some(pub_cyclic_package_dependency) = pub_cyclic_package_dependency {
-            if cyclic_package_dependency != pub_cyclic_package_dependency {
-                dp.make_index_ron_file();
+        // A resolution that ran out of budget says nothing about whether it would have succeeded.
+        let timed_out = PubOutcome::new(res.as_ref()) == PubOutcome::Timeout
+            || CargoOutcome::new(cargo_out.as_ref()) == CargoOutcome::Timeout;
+        if let Some(pub_cyclic_package_dependency) =
+            pub_cyclic_package_dependency.filter(|_| !timed_out)
+        {
+            if cyclic_package_dependency && !forbidden_cycles.is_empty() && pub_cycle.is_none() {
+                // PubGrub was asked to avoid the cycle cargo reports, so disagreeing is expected.
+                if res.as_ref().unwrap().is_ok() {
+                    let fixture = dp.make_index_ron_file();
+                    mismatches.push(Mismatch {
+                        cycle: forbidden_cycles[0].clone(),
+                        ..Mismatch::new(
+                            crt,
+                            &ver,
+                            MismatchKind::CycleAvoided,
+                            None,
+                            cargo_out.as_ref(),
+                            fixture,
+                        )
+                    });
+                }
+            } else if cyclic_package_dependency != pub_cyclic_package_dependency {
+                let fixture = dp.make_index_ron_file();
                 println!("failed to cyclic_package_dependency {root:?}");
+                if let Some(cycle) = &pub_cycle {
+                    println!("{}", CheckError::Cycle(cycle.clone()));
+                }
+                mismatches.push(Mismatch {
+                    cycle: pub_cycle.clone().unwrap_or_default(),
+                    ..Mismatch::new(
+                        crt,
+                        &ver,
+                        MismatchKind::CyclicDisagreement,
+                        res.as_ref(),
+                        cargo_out.as_ref(),
+                        fixture,
+                    )
+                });
             }
 
             if !cyclic_package_dependency
                 && res.as_ref().unwrap().is_ok() != cargo_out.as_ref().unwrap().is_ok()
             {
-                dp.make_index_ron_file();
+                let fixture = dp.make_index_ron_file();
                 println!("failed to match cargo {root:?}");
+                mismatches.push(Mismatch::new(
+                    crt,
+                    &ver,
+                    MismatchKind::SuccessDisagreement,
+                    res.as_ref(),
+                    cargo_out.as_ref(),
+                    fixture,
+                ));
             }
         }
     }
+    let mut solution_only_pub = None;
+    let mut solution_only_cargo = None;
+    if compare.solutions {
+        if let (Some(Ok(pub_map)), Some(Ok(cargo_map))) = (&res, &cargo_out) {
+            let pub_set = pub_solution(pub_map);
+            let cargo_set = cargo_solution(cargo_map);
+            let only_pub = pub_set
+                .difference(&cargo_set)
+                .map(|(n, v)| format!("{n}@{v}"))
+                .collect_vec();
+            let only_cargo = cargo_set
+                .difference(&pub_set)
+                .map(|(n, v)| format!("{n}@{v}"))
+                .collect_vec();
+            solution_only_pub = Some(only_pub.len());
+            solution_only_cargo = Some(only_cargo.len());
+            if compare.dump_solution_diffs && !(only_pub.is_empty() && only_cargo.is_empty()) {
+                let fixture = dp.make_index_ron_file();
+                mismatches.push(Mismatch {
+                    only_pub,
+                    only_cargo,
+                    ..Mismatch::new(
+                        crt,
+                        &ver,
+                        MismatchKind::SolutionDifference,
+                        None,
+                        None,
+                        fixture,
+                    )
+                });
+            }
+        }
+    }
+
+    let mut feature_diffs = None;
+    if compare.features {
+        if let (Some(Ok(pub_map)), Some(Ok(cargo_map))) = (&res, &cargo_out) {
+            let pub_features = dp.pub_features(pub_map);
+            let cargo_features = cargo_features(cargo_map);
+            let features = pub_features
+                .iter()
+                .filter_map(|(id, pub_feats)| {
+                    let cargo_feats = cargo_features.get(id)?;
+                    if pub_feats == cargo_feats {
+                        return None;
+                    }
+                    Some(FeatureDiff {
+                        package: format!("{}@{}", id.0, id.1),
+                        missing: cargo_feats.difference(pub_feats).copied().collect(),
+                        extra: pub_feats.difference(cargo_feats).copied().collect(),
+                    })
+                })
+                .collect_vec();
+            feature_diffs = Some(features.len());
+            if !features.is_empty() {
+                let fixture = dp.make_index_ron_file();
+                println!("failed to match cargo features {root:?}");
+                mismatches.push(Mismatch {
+                    features,
+                    ..Mismatch::new(
+                        crt,
+                        &ver,
+                        MismatchKind::FeatureDifference,
+                        None,
+                        None,
+                        fixture,
+                    )
+                });
+            }
+        }
+    }
+
     let mut cargo_check_pub_lock_time = 0.0;
     if mode.build_cargo_lock() && res.as_ref().unwrap().is_ok() {
-        dp.past_result = res
-            .as_ref()
-            .unwrap()
-            .as_ref()
-            .map(|map| {
-                let mut results: HashMap<
-                    InternedString,
-                    BTreeSet<semver::Version>,
-                    rustc_hash::FxBuildHasher,
-                > = HashMap::default();
-                for (k, v) in map.iter() {
-                    if k.is_real() {
-                        results.entry(k.crate_()).or_default().insert(v.clone());
-                    }
-                }
-                results
-            })
-            .ok();
+        dp.past_result = res.as_ref().unwrap().as_ref().map(pub_lock).ok();
         dp.reset_time();
-        let cargo_check_pub_lock_out = cargo_resolver::resolve(crt, &ver, dp);
+        let cargo_check_pub_lock_out = cargo_resolver::resolve(&root, &ver, dp);
         cargo_check_pub_lock_time = dp.duration();
 
         let cyclic_package_dependency_pub_lock = &cargo_check_pub_lock_out
@@ -1030,40 +2189,40 @@ pub fn process_crate_version(
             .map_err(|e| e.to_string().starts_with("cyclic package dependency"))
             == &Err(true);
 
-        if !cyclic_package_dependency_pub_lock && !cargo_check_pub_lock_out.is_ok() {
-            dp.make_index_ron_file();
+        let timed_out = CargoOutcome::new(Some(&cargo_check_pub_lock_out)) == CargoOutcome::Timeout;
+        if !cyclic_package_dependency_pub_lock && !timed_out && !cargo_check_pub_lock_out.is_ok() {
+            let fixture = dp.make_index_ron_file();
             println!("failed to match pub lock cargo {root:?}");
+            mismatches.push(Mismatch::new(
+                crt,
+                &ver,
+                MismatchKind::CargoRejectsPubLock,
+                None,
+                Some(&cargo_check_pub_lock_out),
+                fixture,
+            ));
         }
     }
 
     let mut pub_check_cargo_lock_time = 0.0;
     if mode.build_pub_lock() && cargo_out.as_ref().unwrap().is_ok() {
-        dp.past_result = cargo_out
-            .as_ref()
-            .unwrap()
-            .as_ref()
-            .map(|map| {
-                let mut results: HashMap<
-                    InternedString,
-                    BTreeSet<semver::Version>,
-                    rustc_hash::FxBuildHasher,
-                > = HashMap::default();
-                for v in map.iter() {
-                    results
-                        .entry(v.name())
-                        .or_default()
-                        .insert(v.version().clone());
-                }
-                results
-            })
-            .ok();
+        dp.past_result = cargo_out.as_ref().unwrap().as_ref().map(cargo_lock).ok();
         dp.reset_time();
         let pub_check_cargo_lock_out = resolve(dp, root.clone(), ver.clone());
         pub_check_cargo_lock_time = dp.duration();
 
-        if !pub_check_cargo_lock_out.is_ok() {
-            dp.make_index_ron_file();
+        let timed_out = PubOutcome::new(Some(&pub_check_cargo_lock_out)) == PubOutcome::Timeout;
+        if !timed_out && !pub_check_cargo_lock_out.is_ok() {
+            let fixture = dp.make_index_ron_file();
             println!("failed to match cargo lock pub {root:?}");
+            mismatches.push(Mismatch::new(
+                crt,
+                &ver,
+                MismatchKind::PubRejectsCargoLock,
+                Some(&pub_check_cargo_lock_out),
+                None,
+                fixture,
+            ));
         }
     }
 
@@ -1085,9 +2244,51 @@ pub fn process_crate_version(
         0
     };
 
+    let pub_outcome = PubOutcome::new(res.as_ref());
+    let cargo_outcome = CargoOutcome::new(cargo_out.as_ref());
+    // The outcomes that did not finish come first, as they can not be compared.
+    let status = match (pub_outcome, cargo_outcome) {
+        (PubOutcome::Timeout, _) | (PubOutcome::Skipped, CargoOutcome::Timeout) => Status::Timeout,
+        // Errors from our own `DependencyProvider` are bugs, so they are recorded like a crash.
+        (PubOutcome::Error, _) => Status::Panic,
+        _ if mismatches.iter().any(|m| {
+            !matches!(
+                m.kind,
+                MismatchKind::SolutionDifference | MismatchKind::CycleAvoided
+            )
+        }) =>
+        {
+            Status::Mismatch
+        }
+        (PubOutcome::Ok, _) => Status::Ok,
+        (PubOutcome::NoSolution, _) => Status::NoSolution,
+        (PubOutcome::Skipped, CargoOutcome::Ok | CargoOutcome::Skipped) => Status::Ok,
+        (PubOutcome::Skipped, _) => Status::NoSolution,
+    };
+    let pub_explanation = match &res {
+        Some(Err(PubGrubError::NoSolution(derivation))) => Some(explain::explain(derivation)),
+        _ => None,
+    };
+    // With its causes, like `Mismatch::cargo_error`.
+    let cargo_error = match &cargo_out {
+        Some(Err(e)) => Some(format!("{e:#}")),
+        _ => None,
+    };
+    let status_message = match &res {
+        Some(Err(e)) if pub_outcome == PubOutcome::Error => Some(e.to_string()),
+        _ => None,
+    };
+
     OutputSummary {
         name: crt,
         ver,
+        config: dp.config(),
+        status,
+        status_message,
+        pub_outcome,
+        cargo_outcome,
+        pub_explanation,
+        cargo_error,
         time: pub_time,
         succeeded: matches!(&res, Some(Ok(_))),
         should_cancel_call_count,
@@ -1096,9 +2297,344 @@ pub fn process_crate_version(
         deps,
         cargo_time,
         cyclic_package_dependency,
+        forbidden_cycles: forbidden_cycles.len(),
         cargo_deps,
         cargo_check_pub_lock_time,
         pub_check_cargo_lock_time,
+        solution_only_pub,
+        solution_only_cargo,
+        feature_diffs,
+        mismatches,
+    }
+}
+
+pub fn print_root<'c>(
+    dp: &mut Index<'c>,
+    root: Names<'c>,
+    ver: semver::Version,
+    mode: Mode,
+    synthetic: bool,
+    lockfiles: Option<&str>,
+) {
+    let write_lockfile = |resolver: &str, lockfile: String| {
+        let Some(dir) = lockfiles else {
+            return;
+        };
+        std::fs::create_dir_all(dir).unwrap();
+        let file_name = format!("{dir}/{}@{ver}.{resolver}.lock", root.crate_());
+        std::fs::write(&file_name, lockfile).unwrap();
+        println!("Wrote {file_name}");
+    };
+    dp.reset();
+    let mut res = None;
+    if mode.build_pub() {
+        let checked = resolve_and_check(dp, root.clone(), &ver);
+        let out = checked.res;
+        println!(
+            "PubGrub: {:?} in {:.3}s, {} get_dependencies, {} should_cancel",
+            PubOutcome::new(Some(&out)),
+            checked.time,
+            dp.pubgrub_dependencies.borrow().len(),
+            dp.should_cancel_call_count(),
+        );
+        for cycle in &checked.forbidden_cycles {
+            println!("Forbidden: {}", CheckError::Cycle(cycle.clone()));
+        }
+        if let Some(cycle) = checked.cycle {
+            println!("{}", CheckError::Cycle(cycle));
+        }
+        if let Some(e) = &checked.check_error {
+            println!("failed check: {e}");
+        }
+        match &out {
+            Ok(map) if synthetic => {
+                for (name, v) in map.iter().sorted_by(|a, b| a.0.cmp(b.0)) {
+                    println!("  {name} = {v}");
+                }
+            }
+            Ok(map) => {
+                let features = dp.pub_features(map);
+                for (name, v) in pub_solution(map) {
+                    let feats = features.get(&(name, v.clone()));
+                    println!(
+                        "  {name}@{v} {:?}",
+                        feats.into_iter().flatten().collect_vec()
+                    );
+                }
+            }
+            Err(PubGrubError::NoSolution(derivation)) => {
+                println!("{}", explain::explain(derivation));
+                if synthetic {
+                    println!("{}", DefaultStringReporter::report(derivation));
+                }
+            }
+            Err(e) => println!("{e}"),
+        }
+        // `pub_lockfile` needs a valid solution.
+        match (&out, &checked.check_error) {
+            (Ok(map), None) => write_lockfile("pubgrub", lockfile::pub_lockfile(dp, map)),
+            (Ok(_), Some(_)) if lockfiles.is_some() => {
+                println!("Skipped the PubGrub lockfile, as the solution failed the check")
+            }
+            _ => {}
+        }
+        res = Some(out);
+    }
+
+    let mut cargo_out = None;
+    if mode.build_cargo() {
+        dp.reset_time();
+        let out = cargo_resolver::resolve(&root, &ver, dp);
+        println!(
+            "Cargo: {:?} in {:.3}s",
+            CargoOutcome::new(Some(&out)),
+            dp.duration(),
+        );
+        match &out {
+            Ok(cargo_map) => {
+                for ((name, v), feats) in cargo_features(cargo_map) {
+                    println!("  {name}@{v} {:?}", feats.into_iter().collect_vec());
+                }
+            }
+            Err(e) => println!("{e:?}"),
+        }
+        if let Ok(cargo_map) = &out {
+            write_lockfile("cargo", lockfile::cargo_lockfile(cargo_map));
+        }
+        cargo_out = Some(out);
+    }
+
+    if mode.build_cargo_lock() {
+        if let Some(Ok(pub_map)) = &res {
+            dp.past_result = Some(pub_lock(pub_map));
+            dp.reset_time();
+            let out = cargo_resolver::resolve(&root, &ver, dp);
+            println!(
+                "Cargo with the PubGrub lock: {:?} in {:.3}s",
+                CargoOutcome::new(Some(&out)),
+                dp.duration(),
+            );
+            if let Err(e) = &out {
+                println!("{e:?}");
+            }
+        }
+    }
+
+    if mode.build_pub_lock() {
+        if let Some(Ok(cargo_map)) = &cargo_out {
+            dp.past_result = Some(cargo_lock(cargo_map));
+            dp.reset_time();
+            let out = resolve(dp, root.clone(), ver.clone());
+            println!(
+                "PubGrub with the cargo lock: {:?} in {:.3}s",
+                PubOutcome::new(Some(&out)),
+                dp.duration(),
+            );
+            if let Err(e) = &out {
+                println!("{e}");
+            }
+        }
+    }
+}
+
+fn pub_solution(map: &SelectedDependencies<Index>) -> BTreeSet<(InternedString, semver::Version)> {
+    map.iter()
+        .filter(|(k, _)| k.is_real())
+        .map(|(k, v)| (k.crate_(), v.clone()))
+        .collect()
+}
+
+fn pub_lock(map: &SelectedDependencies<Index>) -> Lock {
+    let mut results = Lock::default();
+    for (k, v) in map.iter() {
+        if k.is_real() {
+            results.entry(k.crate_()).or_default().insert(v.clone());
+        }
+    }
+    results
+}
+
+fn cargo_lock(resolve: &Resolve) -> Lock {
+    let mut results = Lock::default();
+    for v in resolve.iter() {
+        results
+            .entry(v.name())
+            .or_default()
+            .insert(v.version().clone());
+    }
+    results
+}
+
+fn cargo_features(
+    resolve: &Resolve,
+) -> BTreeMap<(InternedString, semver::Version), BTreeSet<InternedString>> {
+    let mut out: BTreeMap<_, BTreeSet<_>> = BTreeMap::new();
+    for p in resolve.iter() {
+        out.entry((p.name(), p.version().clone()))
+            .or_default()
+            .extend(resolve.features(p).iter().copied());
+    }
+    out
+}
+
+fn cargo_solution(resolve: &Resolve) -> BTreeSet<(InternedString, semver::Version)> {
+    resolve
+        .iter()
+        .map(|p| (p.name(), p.version().clone()))
+        .collect()
+}
+
+#[derive(serde::Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Ok,
+    NoSolution,
+    Timeout,
+    Panic,
+    Mismatch,
+}
+
+type PubResult<'c> = Result<SelectedDependencies<Index<'c>>, PubGrubError<Index<'c>>>;
+
+#[derive(serde::Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PubOutcome {
+    Skipped,
+    Ok,
+    NoSolution,
+    Timeout,
+    Error,
+}
+
+impl PubOutcome {
+    fn new(res: Option<&PubResult>) -> Self {
+        match res {
+            None => PubOutcome::Skipped,
+            Some(Ok(_)) => PubOutcome::Ok,
+            Some(Err(PubGrubError::NoSolution(_))) => PubOutcome::NoSolution,
+            Some(Err(PubGrubError::ErrorInShouldCancel(_))) => PubOutcome::Timeout,
+            Some(Err(_)) => PubOutcome::Error,
+        }
+    }
+}
+
+#[derive(serde::Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CargoOutcome {
+    Skipped,
+    Ok,
+    Cyclic,
+    NoCandidates,
+    LinksConflict,
+    Timeout,
+    Error,
+}
+
+impl CargoOutcome {
+    fn new(res: Option<&CargoResult<Resolve>>) -> Self {
+        let Some(res) = res else {
+            return CargoOutcome::Skipped;
+        };
+        let Err(e) = res else {
+            return CargoOutcome::Ok;
+        };
+        if e.chain().any(|e| e.is::<SomeError>()) {
+            return CargoOutcome::Timeout;
+        }
+        let msg = e.to_string();
+        if msg.starts_with("cyclic package dependency") {
+            CargoOutcome::Cyclic
+        } else if msg.contains("links to the native library") {
+            CargoOutcome::LinksConflict
+        } else if msg.starts_with("failed to select a version")
+            || msg.starts_with("no matching package")
+        {
+            CargoOutcome::NoCandidates
+        } else {
+            CargoOutcome::Error
+        }
+    }
+}
+
+#[derive(serde::Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MismatchKind {
+    CyclicDisagreement,
+    SuccessDisagreement,
+    CargoRejectsPubLock,
+    PubRejectsCargoLock,
+    /// This is not a bug, as neither promises to find the same "best" solution.
+    SolutionDifference,
+    FeatureDifference,
+    InvalidSolution,
+    /// Cargo found a cyclic package dependency, which PubGrub resolved around because of `Index::with_forbid_cycles`.
+    /// This is not a bug, but shows where the cycle rule changes the outcome.
+    CycleAvoided,
+}
+
+#[derive(serde::Serialize, Debug)]
+pub struct FeatureDiff {
+    pub package: String,
+    pub missing: Vec<InternedString>,
+    pub extra: Vec<InternedString>,
+}
+
+#[derive(serde::Serialize, Debug)]
+pub struct Mismatch {
+    pub name: InternedString,
+    pub ver: semver::Version,
+    pub kind: MismatchKind,
+    pub pub_error: Option<String>,
+    pub cargo_error: Option<String>,
+    pub pub_explanation: Option<String>,
+    pub fixture: String,
+    pub derivation_tree: Option<String>,
+    pub check_error: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub cycle: Vec<CycleStep>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub only_pub: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub only_cargo: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub features: Vec<FeatureDiff>,
+}
+
+impl Mismatch {
+    fn new(
+        name: InternedString,
+        ver: &semver::Version,
+        kind: MismatchKind,
+        pub_res: Option<&PubResult>,
+        cargo_res: Option<&CargoResult<Resolve>>,
+        fixture: String,
+    ) -> Self {
+        let (derivation_tree, pub_explanation) = match pub_res {
+            Some(Err(PubGrubError::NoSolution(derivation))) => (
+                Some(DefaultStringReporter::report(derivation)),
+                Some(explain::explain(derivation)),
+            ),
+            _ => (None, None),
+        };
+        Self {
+            name,
+            ver: ver.clone(),
+            kind,
+            pub_error: pub_res
+                .and_then(|r| r.as_ref().err())
+                .map(|e| e.to_string()),
+            cargo_error: cargo_res
+                .and_then(|r| r.as_ref().err())
+                .map(|e| format!("{e:#}")),
+            pub_explanation,
+            fixture,
+            derivation_tree,
+            check_error: None,
+            cycle: vec![],
+            only_pub: vec![],
+            only_cargo: vec![],
+            features: vec![],
+        }
     }
 }
 
@@ -1106,6 +2642,13 @@ pub fn process_crate_version(
 pub struct OutputSummary {
     pub name: InternedString,
     pub ver: semver::Version,
+    pub config: String,
+    pub status: Status,
+    pub status_message: Option<String>,
+    pub pub_outcome: PubOutcome,
+    pub cargo_outcome: CargoOutcome,
+    pub pub_explanation: Option<String>,
+    pub cargo_error: Option<String>,
     pub time: f32,
     pub succeeded: bool,
     pub should_cancel_call_count: u64,
@@ -1114,7 +2657,51 @@ pub struct OutputSummary {
     pub deps: usize,
     pub cargo_time: f32,
     pub cyclic_package_dependency: bool,
+    pub forbidden_cycles: usize,
     pub cargo_deps: usize,
     pub cargo_check_pub_lock_time: f32,
     pub pub_check_cargo_lock_time: f32,
+    pub solution_only_pub: Option<usize>,
+    pub solution_only_cargo: Option<usize>,
+    pub feature_diffs: Option<usize>,
+    /// Written to a separate JSON Lines file, one record per discrepancy.
+    #[serde(skip)]
+    pub mismatches: Vec<Mismatch>,
+}
+
+impl OutputSummary {
+    pub fn panicked(
+        name: InternedString,
+        ver: semver::Version,
+        config: String,
+        message: String,
+    ) -> Self {
+        Self {
+            name,
+            ver,
+            config,
+            status: Status::Panic,
+            status_message: Some(message),
+            pub_outcome: PubOutcome::Skipped,
+            cargo_outcome: CargoOutcome::Skipped,
+            pub_explanation: None,
+            cargo_error: None,
+            time: 0.0,
+            succeeded: false,
+            should_cancel_call_count: 0,
+            get_dependencies_call_count: 0,
+            pubgrub_deps: 0,
+            deps: 0,
+            cargo_time: 0.0,
+            cyclic_package_dependency: false,
+            forbidden_cycles: 0,
+            cargo_deps: 0,
+            cargo_check_pub_lock_time: 0.0,
+            pub_check_cargo_lock_time: 0.0,
+            solution_only_pub: None,
+            solution_only_cargo: None,
+            feature_diffs: None,
+            mismatches: vec![],
+        }
+    }
 }
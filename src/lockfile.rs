@@ -0,0 +1,221 @@
+//! Reads and writes `Cargo.lock` files.
+//!
+//! Reading only keeps the crate versions, to prefer when resolving, see `Index::with_prefer_locked`.
+//! Writing turns a solution of PubGrub or cargo into a cargo `Resolve`, serialized by cargo,
+//! so that the `Cargo.lock` of both can be compared with `diff`.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::Path;
+
+use anyhow::{bail, Context as _};
+use cargo::{
+    core::{PackageId, Resolve, ResolveVersion, SourceId},
+    sources::CRATES_IO_INDEX,
+    util::{interning::InternedString, Graph, IntoUrl},
+};
+use cargo_util_schemas::manifest::RustVersion;
+use crates_index::DependencyKind;
+use pubgrub::SelectedDependencies;
+use semver_pubgrub::SemverCompatibility;
+
+use crate::{
+    cargo_resolver,
+    names::{new_bucket, FeatureNamespace, Names},
+    Index, Lock,
+};
+
+/// Reads the name and version of each `[[package]]` in the `Cargo.lock` at `path`.
+pub fn read_lock(path: &str) -> anyhow::Result<Lock> {
+    let text = std::fs::read_to_string(path).with_context(|| format!("reading {path}"))?;
+    parse_lock(&text).with_context(|| format!("parsing {path}"))
+}
+
+/// Reads the name and version of each `[[package]]` in the text of a `Cargo.lock`.
+///
+/// Packages that are not from the index, like the root, are read as well,
+/// which does no harm as only the versions in the index data can be selected.
+pub fn parse_lock(text: &str) -> anyhow::Result<Lock> {
+    let toml: toml::Table = text.parse()?;
+    let mut lock = Lock::default();
+    let packages = toml.get("package").and_then(|p| p.as_array());
+    for package in packages.into_iter().flatten() {
+        let name = package.get("name").and_then(|n| n.as_str());
+        let version = package.get("version").and_then(|v| v.as_str());
+        let (Some(name), Some(version)) = (name, version) else {
+            bail!("a `[[package]]` without a `name` and `version`");
+        };
+        let version = version
+            .parse()
+            .with_context(|| format!("parsing the version of `{name}`"))?;
+        lock.entry(InternedString::new(name))
+            .or_default()
+            .insert(version);
+    }
+    Ok(lock)
+}
+
+/// A package in a `Cargo.lock`, and whether it is a root.
+///
+/// Roots are locked as path dependencies, which have no `source`, the others as crates.io dependencies.
+type LockedId = (InternedString, semver::Version, bool);
+
+/// The `Cargo.lock` of a PubGrub solution.
+///
+/// The dependencies of each crate version are the ones PubGrub used,
+/// each locked to the version selected for its compatibility range.
+/// Renamed dependencies are locked by their `package_name`, as cargo does.
+pub fn pub_lockfile<'c>(dp: &Index<'c>, pubmap: &SelectedDependencies<Index<'c>>) -> String {
+    // The optional dependencies activated on each bucket that is not a root.
+    let mut activated: HashMap<(InternedString, SemverCompatibility), BTreeSet<&str>> =
+        HashMap::new();
+    for names in pubmap.keys() {
+        if let Names::BucketFeatures(name, compat, FeatureNamespace::Dep(dep)) = names {
+            activated.entry((*name, *compat)).or_default().insert(*dep);
+        }
+    }
+
+    let mut graph = BTreeMap::new();
+    let mut checksums = HashMap::new();
+    for (names, ver) in pubmap {
+        let (index_ver, is_root, optional_deps) = match names {
+            Names::Bucket(_, _, true) | Names::Root(_) => {
+                let index_ver = dp.root_version(names, ver).unwrap();
+                let (_, optional_deps) = dp.root_activated(index_ver).unwrap_or_default();
+                (index_ver, true, optional_deps)
+            }
+            Names::Bucket(name, compat, false) => {
+                let index_ver = dp.get_version(name.as_str(), ver).unwrap();
+                let optional_deps = activated.remove(&(*name, *compat)).unwrap_or_default();
+                (index_ver, false, optional_deps)
+            }
+            _ => continue,
+        };
+        let deps = index_ver
+            .deps
+            .iter()
+            .filter(|dep| dep.kind != DependencyKind::Dev || (is_root && dp.root_dev_deps))
+            .filter(|dep| dp.on_target(dep))
+            .filter(|dep| !dep.optional || optional_deps.contains(dep.name.as_str()))
+            .map(|dep| {
+                let (cray, _) = dp.from_dep(dep, index_ver.name, ver);
+                let compat = SemverCompatibility::from(&pubmap[&cray]);
+                let dep_ver = &pubmap[&new_bucket(dep.package_name, compat, false)];
+                (dep.package_name, dep_ver.clone(), false)
+            })
+            .collect();
+        let id = (names.crate_(), ver.clone(), is_root);
+        if !is_root {
+            let checksum = dp.crates[&names.crate_()][ver].1.checksum();
+            checksums.insert(id.clone(), checksum.map(|c| c.to_string()));
+        }
+        graph.insert(id, deps);
+    }
+    let rust_version = dp
+        .rust_version
+        .as_ref()
+        .and_then(|v| v.to_string().parse::<RustVersion>().ok());
+    render(
+        &graph,
+        checksums,
+        ResolveVersion::with_rust_version(rust_version.as_ref()),
+    )
+}
+
+/// The `Cargo.lock` of a cargo solution, with its packages moved to the sources of `pub_lockfile`.
+pub fn cargo_lockfile(resolve: &Resolve) -> String {
+    let id =
+        |p: PackageId| -> LockedId { (p.name(), p.version().clone(), cargo_resolver::is_root(p)) };
+    let graph = resolve
+        .iter()
+        .map(|p| (id(p), resolve.deps(p).map(|(d, _)| id(d)).collect()))
+        .collect();
+    let checksums = resolve
+        .iter()
+        .filter(|&p| !cargo_resolver::is_root(p))
+        .map(|p| (id(p), resolve.checksums().get(&p).cloned().flatten()))
+        .collect();
+    render(&graph, checksums, resolve.version())
+}
+
+/// Builds the `Resolve` of `graph` and writes it the way cargo writes a `Cargo.lock`.
+///
+/// `checksums` has an entry for each package that is not a root, as cargo expects,
+/// which is `None` for index data that does not keep them, like the test files.
+fn render(
+    graph: &BTreeMap<LockedId, BTreeSet<LockedId>>,
+    checksums: HashMap<LockedId, Option<String>>,
+    version: ResolveVersion,
+) -> String {
+    let registry = SourceId::for_registry(&CRATES_IO_INDEX.into_url().unwrap()).unwrap();
+    // Cargo does not write the path of a path dependency, so any path will do.
+    let path = SourceId::for_path(Path::new("/")).unwrap();
+    let package_id = |(name, ver, is_root): &LockedId| {
+        PackageId::new(*name, ver.clone(), if *is_root { path } else { registry })
+    };
+    let mut deps = Graph::new();
+    for (id, id_deps) in graph {
+        deps.add(package_id(id));
+        for dep in id_deps {
+            deps.link(package_id(id), package_id(dep));
+        }
+    }
+    let checksums = checksums
+        .iter()
+        .map(|(id, checksum)| (package_id(id), checksum.clone()))
+        .collect();
+    let resolve = Resolve::new(
+        deps,
+        Default::default(),
+        Default::default(),
+        checksums,
+        Default::default(),
+        Vec::new(),
+        version,
+        Default::default(),
+    );
+    serialize_resolve(&resolve)
+}
+
+/// Formats `resolve` as cargo's `resolve_to_string` does, which needs a `Workspace`.
+///
+/// Cargo serializes the `Resolve`, but the layout of the file is done by hand, as here.
+fn serialize_resolve(resolve: &Resolve) -> String {
+    let toml = toml::Table::try_from(resolve).unwrap();
+    let mut out = "# This file is automatically @generated by Cargo.\n".to_string();
+    out += "# It is not intended for manual editing.\n";
+    if let Some(version) = toml.get("version") {
+        out += &format!("version = {version}\n\n");
+    }
+    let packages = toml.get("package").and_then(|p| p.as_array());
+    for package in packages.into_iter().flatten() {
+        let package = package.as_table().unwrap();
+        out += "[[package]]\n";
+        for key in ["name", "version", "source", "checksum"] {
+            if let Some(value) = package.get(key) {
+                out += &format!("{key} = {value}\n");
+            }
+        }
+        let deps = package.get("dependencies").and_then(|d| d.as_array());
+        if let Some(deps) = deps.filter(|deps| !deps.is_empty()) {
+            out += "dependencies = [\n";
+            for dep in deps {
+                out += &format!(" {dep},\n");
+            }
+            out += "]\n";
+        }
+        out += "\n";
+    }
+    if let Some(metadata) = toml.get("metadata") {
+        // Version 1 keeps the checksums here.
+        let mut table = toml::Table::new();
+        table.insert("metadata".to_string(), metadata.clone());
+        out += &table.to_string();
+    }
+    // Like cargo, only version 1 keeps the trailing blank lines.
+    if resolve.version() >= ResolveVersion::V2 {
+        while out.ends_with("\n\n") {
+            out.pop();
+        }
+    }
+    out
+}
@@ -1,11 +1,30 @@
-use std::{sync::mpsc, thread, time::Instant};
+use std::{
+    any::Any,
+    collections::HashSet,
+    fs::OpenOptions,
+    io::{BufWriter, Write as _},
+    panic::{self, AssertUnwindSafe},
+    sync::mpsc,
+    thread,
+    time::Instant,
+};
 
 use crossbeam::channel::unbounded;
 
 use benchmark_from_crates::{
-    index_data, process_crate_version, read_index::read_index, Index, Mode, OutputSummary,
+    index_data::{self, parse_rust_version},
+    lockfile::read_lock,
+    manifest::read_manifest,
+    minimize::{minimize, Predicate},
+    names::{new_bucket, Names},
+    print_root, process_crate_version,
+    read_index::read_index,
+    target::Target,
+    write_index_ron_file, Budget, Compare, Index, Lock, Mode, OutputSummary, RootFeatures,
+    VersionsOrder,
 };
-use clap::Parser;
+use cargo::util::interning::InternedString;
+use clap::{Parser, Subcommand};
 use indicatif::{ProgressBar, ProgressFinish, ProgressStyle};
 use rayon::iter::{IntoParallelRefIterator as _, ParallelIterator as _};
 use time::{format_description::well_known::Rfc3339, OffsetDateTime};
@@ -13,6 +32,9 @@ use time::{format_description::well_known::Rfc3339, OffsetDateTime};
 #[derive(Parser, Debug)]
 #[command(about, long_about = None)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Dont filter out core elements of the Solana ecosystem
     #[clap(long)]
     with_solana: bool,
@@ -20,6 +42,31 @@ struct Args {
     #[arg(long, short, value_enum, default_value_t = Mode::All)]
     mode: Mode,
 
+    /// Which features of the root to activate: `all`, `default`, `no-default`, or a comma separated list of features.
+    #[clap(long, default_value_t = RootFeatures::All)]
+    root_features: RootFeatures,
+
+    /// Do not use the dev-dependencies of the root.
+    #[clap(long)]
+    no_root_dev_deps: bool,
+
+    /// Leave out the dependencies that are not used on this target triple, instead of resolving for every platform.
+    #[clap(long)]
+    target: Option<String>,
+
+    /// Prefer the crate versions that support this Rust version, like cargo's MSRV-aware resolver.
+    #[clap(long, value_parser = rust_version_arg)]
+    rust_version: Option<semver::Version>,
+
+    /// Which version of each crate to try first, `minimal` being like `cargo -Z minimal-versions`
+    /// and `direct-minimal` like `cargo -Z direct-minimal-versions`.
+    #[arg(long, value_enum, default_value_t = VersionsOrder::Maximal)]
+    versions_order: VersionsOrder,
+
+    /// Try the crate versions in this `Cargo.lock` first, like `cargo update -p` keeps the rest of the lock.
+    #[clap(long)]
+    prefer_locked: Option<String>,
+
     /// Sets the number of threads to be used in the rayon threadpool.
     #[clap(long, short, default_value_t = 0)]
     threads: usize,
@@ -31,10 +78,234 @@ struct Args {
     /// Use a particular refspec from the index to process.
     #[clap(long)]
     commit: Option<String>,
+
+    /// Seconds after which a single resolution is canceled.
+    #[clap(long, default_value_t = Budget::default().time_cut_off)]
+    timeout: f32,
+
+    /// Seconds after which the index data used by a resolution is saved to `out/index_ron`.
+    #[clap(long, default_value_t = Budget::default().time_make_file)]
+    make_file_after: f32,
+
+    /// Cancel a resolution after this many distinct dependency lookups.
+    #[clap(long)]
+    max_get_dependencies: Option<usize>,
+
+    /// Compare the crate versions selected by PubGrub and cargo.
+    #[clap(long)]
+    compare_solutions: bool,
+
+    /// Report the crate versions selected by only one of PubGrub or cargo. Implies `--compare-solutions`.
+    #[clap(long)]
+    dump_solution_diffs: bool,
+
+    /// Compare the features activated by PubGrub and cargo on the crate versions they both selected.
+    #[clap(long)]
+    compare_features: bool,
+
+    /// When PubGrub's solution has a cyclic package dependency, forbid it and resolve again,
+    /// instead of reporting the cycle like cargo does.
+    #[clap(long)]
+    forbid_cycles: bool,
+
+    /// Skip crate versions already recorded in the output file for this index commit, and append the rest.
+    /// Refuses to if the output file was written with other columns.
+    #[clap(long)]
+    resume: bool,
+}
+
+/// The options of the subcommands that print a resolution, see `print_root`.
+#[derive(clap::Args, Debug)]
+struct PrintArgs {
+    /// Also print the packages PubGrub only selects because of how crates are encoded.
+    #[arg(long)]
+    synthetic: bool,
+
+    /// Write the solutions of PubGrub and cargo to this directory as `Cargo.lock` files.
+    #[arg(long)]
+    lockfiles: Option<String>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Shrink a RON file from `out/index_ron` to a smaller one that still fails.
+    Minimize {
+        /// The RON file to shrink.
+        file: String,
+
+        /// The crate version to resolve, as `name@version`.
+        root: String,
+
+        /// Which failure has to keep happening.
+        #[arg(long, value_enum, default_value_t = Predicate::Any)]
+        predicate: Predicate,
+
+        /// Where to write the result. Defaults to `out/minimized/<name>@<version>.ron`.
+        #[arg(long)]
+        out: Option<String>,
+    },
+    /// Resolve a single crate version in `--mode` and print the solutions, timings and any conflict explanation.
+    Resolve {
+        name: String,
+
+        version: semver::Version,
+
+        #[command(flatten)]
+        print: PrintArgs,
+    },
+    /// Resolve a `Cargo.toml`, or a RON file with a single crate version, against the index like `resolve`.
+    Manifest {
+        path: String,
+
+        #[command(flatten)]
+        print: PrintArgs,
+    },
+    /// Resolve several roots together, like the members of a workspace, and print the result like `resolve`.
+    Workspace {
+        /// The crate versions to resolve, as `name@version`.
+        roots: Vec<String>,
+
+        /// A `Cargo.toml`, or a RON file with a single crate version, to resolve as well. Can be repeated.
+        #[arg(long = "manifest")]
+        manifests: Vec<String>,
+
+        #[command(flatten)]
+        print: PrintArgs,
+    },
+}
+
+/// The columns of an existing output row needed to know it does not need to be redone.
+#[derive(serde::Deserialize)]
+struct DoneRow {
+    name: String,
+    ver: semver::Version,
+}
+
+/// The header row `OutputSummary` is written with.
+fn output_headers() -> csv::ByteRecord {
+    let mut writer = csv::Writer::from_writer(vec![]);
+    let row = OutputSummary::panicked(
+        InternedString::new(""),
+        semver::Version::new(0, 0, 0),
+        String::new(),
+        String::new(),
+    );
+    writer.serialize(row).unwrap();
+    let data = writer.into_inner().unwrap();
+    csv::Reader::from_reader(data.as_slice())
+        .byte_headers()
+        .unwrap()
+        .clone()
+}
+
+/// Reads the crate versions already recorded in `file_name`,
+/// and truncates the file after the last complete row so that new rows can be appended.
+///
+/// Exits if the file was written with other columns, as appending to it would mix them up.
+fn read_done(file_name: &str) -> HashSet<(String, semver::Version)> {
+    let mut done = HashSet::new();
+    let mut reader = csv::Reader::from_path(file_name).unwrap();
+    let headers = reader.byte_headers().unwrap().clone();
+    if headers != output_headers() {
+        eprintln!(
+            "{file_name} has other columns than this version writes, move it away to start over"
+        );
+        std::process::exit(1);
+    }
+    let mut record = csv::ByteRecord::new();
+    let mut end_of_complete = reader.position().byte();
+    // The last row may have been cut off by the crash we are resuming from.
+    while let Ok(true) = reader.read_byte_record(&mut record) {
+        let Ok(row) = record.deserialize::<DoneRow>(Some(&headers)) else {
+            break;
+        };
+        done.insert((row.name, row.ver));
+        end_of_complete = reader.position().byte();
+    }
+    OpenOptions::new()
+        .write(true)
+        .open(file_name)
+        .unwrap()
+        .set_len(end_of_complete)
+        .unwrap();
+    done
+}
+
+/// Applies the options in `args` that every resolution uses.
+fn configure<'c>(
+    index: Index<'c>,
+    args: &Args,
+    target: Option<&'c Target>,
+    prefer_locked: Option<&'c Lock>,
+) -> Index<'c> {
+    index
+        .with_budget(Budget {
+            time_cut_off: args.timeout,
+            time_make_file: args.make_file_after,
+            max_get_dependencies: args.max_get_dependencies,
+        })
+        .with_forbid_cycles(args.forbid_cycles)
+        .with_root_features(args.root_features.clone())
+        .with_root_dev_deps(!args.no_root_dev_deps)
+        .with_target(target)
+        .with_rust_version(args.rust_version.clone())
+        .with_versions_order(args.versions_order)
+        .with_prefer_locked(prefer_locked)
+}
+
+fn rust_version_arg(s: &str) -> Result<semver::Version, String> {
+    parse_rust_version(s).ok_or_else(|| format!("`{s}` is not a Rust version like `1.70`"))
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "unknown panic payload".to_string()
+    }
+}
+
+fn parse_root(root: &str) -> (InternedString, semver::Version) {
+    let (name, ver) = root
+        .split_once('@')
+        .expect("the root should be `name@version`");
+    (InternedString::new(name), ver.parse().unwrap())
+}
+
+fn run_minimize(file: &str, root: &str, predicate: Predicate, out: Option<&str>) {
+    let (name, ver) = parse_root(root);
+    let data = std::fs::read_to_string(file).unwrap();
+    let data: Vec<index_data::Version> = ron::de::from_str(&data).unwrap();
+    let len = data.len();
+    let Some(data) = minimize(data, name, &ver, predicate) else {
+        println!("{name}@{ver} does not fail {predicate:?} with {file}");
+        return;
+    };
+    let out = out.map_or_else(
+        || {
+            std::fs::create_dir_all("out/minimized").unwrap();
+            format!("out/minimized/{name}@{ver}.ron")
+        },
+        str::to_string,
+    );
+    write_index_ron_file(&out, &data);
+    println!("Minimized {len} to {} versions in {out}", data.len());
 }
 
 fn main() {
     let args = Args::parse();
+    if let Some(Command::Minimize {
+        file,
+        root,
+        predicate,
+        out,
+    }) = &args.command
+    {
+        run_minimize(file, root, *predicate, out.as_deref());
+        return;
+    }
     rayon::ThreadPoolBuilder::new()
         .num_threads(args.threads)
         .build_global()
@@ -57,29 +328,178 @@ fn main() {
     let mut index =
         crates_index::GitIndex::with_path("index", "https://github.com/rust-lang/crates.io-index")
             .unwrap();
-    if let Some(commit) = args.commit {
-        index.set_commit_from_refspec(&commit).unwrap();
+    if let Some(commit) = &args.commit {
+        index.set_commit_from_refspec(commit).unwrap();
     }
-    let data = read_index(&index, create_filter, version_filter);
+    let target = args
+        .target
+        .as_deref()
+        .map(|triple| Target::from_rustc(triple).unwrap());
+    let data = read_index(&index, create_filter, version_filter, target.as_ref());
+    let prefer_locked = args
+        .prefer_locked
+        .as_deref()
+        .map(|path| read_lock(path).unwrap());
+
+    let manifests: Vec<_> = match &args.command {
+        Some(Command::Manifest { path, .. }) => vec![read_manifest(path).unwrap()],
+        Some(Command::Workspace { manifests, .. }) => manifests
+            .iter()
+            .map(|p| read_manifest(p).unwrap())
+            .collect(),
+        _ => Vec::new(),
+    };
+    let print = match &args.command {
+        Some(Command::Resolve {
+            name,
+            version,
+            print,
+        }) => {
+            let name = InternedString::new(name);
+            let root = new_bucket(name, version.into(), true);
+            Some((root, version.clone(), Vec::new(), print))
+        }
+        Some(Command::Manifest { print, .. }) => {
+            let manifest = &manifests[0];
+            let root = Names::Root(manifest.name);
+            Some((root, (*manifest.vers).clone(), Vec::new(), print))
+        }
+        Some(Command::Workspace { roots, print, .. }) => {
+            let mut members: Vec<_> = roots
+                .iter()
+                .map(|root| {
+                    let (name, ver) = parse_root(root);
+                    (new_bucket(name, (&ver).into(), true), ver)
+                })
+                .collect();
+            members.extend(
+                manifests
+                    .iter()
+                    .map(|m| (Names::Root(m.name), (*m.vers).clone())),
+            );
+            // Named after the members, so that each workspace gets its own file in `out/index_ron`.
+            let name = members
+                .iter()
+                .map(|(member, _)| member.crate_().to_string())
+                .collect::<Vec<_>>()
+                .join("+");
+            let root = Names::Workspace(InternedString::new(&format!("workspace+{name}")));
+            Some((root, semver::Version::new(0, 0, 0), members, print))
+        }
+        Some(Command::Minimize { .. }) | None => None,
+    };
+    if let Some((root, ver, members, print)) = print {
+        let mut index = manifests.iter().fold(
+            configure(
+                Index::new(&data),
+                &args,
+                target.as_ref(),
+                prefer_locked.as_ref(),
+            )
+            .with_workspace(members),
+            |index, manifest| index.with_root(manifest),
+        );
+        print_root(
+            &mut index,
+            root,
+            ver,
+            args.mode,
+            print.synthetic,
+            print.lockfiles.as_deref(),
+        );
+        return;
+    }
+
+    let mut file_name = "out".to_string();
+    if args.with_solana {
+        file_name += "_with_solana";
+    }
+    if let Some(f) = &args.filter {
+        file_name += "_filtered_to_";
+        file_name += f;
+    }
+    if args.root_features != RootFeatures::All {
+        file_name += "_root_features_";
+        file_name += &args.root_features.to_string();
+    }
+    if args.no_root_dev_deps {
+        file_name += "_no_root_dev_deps";
+    }
+    if let Some(t) = &args.target {
+        file_name += "_target_";
+        file_name += t;
+    }
+    if let Some(v) = &args.rust_version {
+        file_name += &format!("_rust_version_{v}");
+    }
+    match args.versions_order {
+        VersionsOrder::Maximal => {}
+        VersionsOrder::Minimal => file_name += "_minimal_versions",
+        VersionsOrder::DirectMinimal => file_name += "_direct_minimal_versions",
+    }
+    if args.prefer_locked.is_some() {
+        file_name += "_prefer_locked";
+    }
+    file_name += "_index_hash_";
+    file_name += &index.commit_hex()[..4];
+    file_name += ".csv";
+
+    let resuming = args.resume && std::fs::metadata(&file_name).map_or(false, |m| m.len() > 0);
+    let done = if resuming {
+        let done = read_done(&file_name);
+        println!(
+            "!!!!!!!!!! Resuming, skipping {} done !!!!!!!!!!",
+            done.len()
+        );
+        done
+    } else {
+        HashSet::new()
+    };
 
     let to_prosses: Vec<_> = data
         .par_iter()
         .filter(|(c, _)| args.filter.as_ref().map_or(true, |f| c.contains(f)))
         .flat_map(|(c, v)| v.par_iter().map(|(v, _)| (c.clone(), v)))
+        .filter(|(c, v)| !done.contains(&(c.to_string(), (*v).clone())))
         .collect();
 
+    let compare = Compare {
+        solutions: args.compare_solutions || args.dump_solution_diffs,
+        dump_solution_diffs: args.dump_solution_diffs,
+        features: args.compare_features,
+    };
+
+    let args = &args;
+    let target = target.as_ref();
+    let prefer_locked = prefer_locked.as_ref();
     thread::scope(|s| {
         let (out_tx, out_rx) = mpsc::channel::<OutputSummary>();
         let (to_prosses_tx, to_prosses_rx) = unbounded();
         for _ in 0..rayon::current_num_threads() {
             let to_prosses_rx = to_prosses_rx.clone();
             let out_tx = out_tx.clone();
-            let mut index = Index::new(&data);
+            let data = &data;
+            let new_index = move || configure(Index::new(data), args, target, prefer_locked);
+            let mut index = new_index();
             s.spawn(move || {
                 for (crt, ver) in to_prosses_rx {
-                    out_tx
-                        .send(process_crate_version(&mut index, crt, ver, args.mode))
-                        .unwrap();
+                    let row = panic::catch_unwind(AssertUnwindSafe(|| {
+                        process_crate_version(&mut index, crt, ver.clone(), args.mode, compare)
+                    }));
+                    let row = match row {
+                        Ok(row) => row,
+                        Err(payload) => {
+                            // The panic may have left the index half way through a resolution.
+                            index = new_index();
+                            OutputSummary::panicked(
+                                crt,
+                                ver,
+                                index.config(),
+                                panic_message(&*payload),
+                            )
+                        }
+                    };
+                    out_tx.send(row).unwrap();
                 }
             });
         }
@@ -97,19 +517,25 @@ fn main() {
             .with_finish(ProgressFinish::AndLeave);
         style.set_length(to_prosses.len() as _);
 
-        let mut file_name = "out".to_string();
-        if args.with_solana {
-            file_name += "_with_solana";
-        }
-        if let Some(f) = args.filter {
-            file_name += "_filtered_to_";
-            file_name += &f;
-        }
-        file_name += "_index_hash_";
-        file_name += &index.commit_hex()[..4];
-        file_name += ".csv";
-
-        let mut out_file = csv::Writer::from_path(&file_name).unwrap();
+        let mut out_file = if resuming {
+            let file = OpenOptions::new().append(true).open(&file_name).unwrap();
+            csv::WriterBuilder::new()
+                .has_headers(false)
+                .from_writer(file)
+        } else {
+            csv::Writer::from_path(&file_name).unwrap()
+        };
+        let mismatch_file_name =
+            file_name.strip_suffix(".csv").unwrap().to_string() + "_mismatches.jsonl";
+        let mut mismatch_file = BufWriter::new(
+            OpenOptions::new()
+                .create(true)
+                .write(true)
+                .append(resuming)
+                .truncate(!resuming)
+                .open(&mismatch_file_name)
+                .unwrap(),
+        );
         let mut pub_cpu_time = 0.0;
         let mut cargo_cpu_time = 0.0;
         let mut cargo_pub_lock_cpu_time = 0.0;
@@ -120,7 +546,14 @@ fn main() {
             cargo_cpu_time += row.cargo_time;
             cargo_pub_lock_cpu_time += row.cargo_check_pub_lock_time;
             pub_cargo_lock_cpu_time += row.pub_check_cargo_lock_time;
-            out_file.serialize(row).unwrap();
+            for mismatch in &row.mismatches {
+                serde_json::to_writer(&mut mismatch_file, mismatch).unwrap();
+                mismatch_file.write_all(b"\n").unwrap();
+            }
+            out_file.serialize(&row).unwrap();
+            // Flush every row, so that a crash only loses the crate versions in progress.
+            out_file.flush().unwrap();
+            mismatch_file.flush().unwrap();
         }
         let wall_time = start.elapsed().as_secs_f32();
         out_file.flush().unwrap();
@@ -0,0 +1,165 @@
+//! Reads a root manifest that is not in the index, see `Index::with_root`.
+//!
+//! Only what the index would record is read. Path, git and workspace dependencies have no version
+//! in the index to resolve against, so they are rejected.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::{bail, Context as _};
+use cargo::util::interning::InternedString;
+use crates_index::DependencyKind;
+
+use crate::index_data;
+
+/// Reads `path` as a `Cargo.toml`, or as a single `index_data::Version` if it ends in `.ron`.
+pub fn read_manifest(path: &str) -> anyhow::Result<index_data::Version> {
+    let text = std::fs::read_to_string(path).with_context(|| format!("reading {path}"))?;
+    if path.ends_with(".ron") {
+        return ron::de::from_str(&text).with_context(|| format!("parsing {path}"));
+    }
+    parse_manifest(&text).with_context(|| format!("reading {path}"))
+}
+
+/// Reads the text of a `Cargo.toml`.
+pub fn parse_manifest(text: &str) -> anyhow::Result<index_data::Version> {
+    let toml: toml::Table = text.parse().context("parsing")?;
+    from_toml(&toml)
+}
+
+fn from_toml(toml: &toml::Table) -> anyhow::Result<index_data::Version> {
+    let package = toml
+        .get("package")
+        .and_then(|p| p.as_table())
+        .context("no `[package]`")?;
+    let name = package
+        .get("name")
+        .and_then(|n| n.as_str())
+        .context("no `package.name`")?;
+    let vers: semver::Version = match package.get("version") {
+        Some(v) => v
+            .as_str()
+            .context("`package.version` is not a string")?
+            .parse()?,
+        // Cargo defaults to this since `package.version` became optional.
+        None => semver::Version::new(0, 0, 0),
+    };
+    let links = package
+        .get("links")
+        .and_then(|l| l.as_str())
+        .map(InternedString::new);
+    let rust_version = package
+        .get("rust-version")
+        .and_then(|r| r.as_str())
+        .map(InternedString::new);
+
+    let mut deps = Vec::new();
+    read_deps(toml, None, &mut deps)?;
+    if let Some(targets) = toml.get("target").and_then(|t| t.as_table()) {
+        for (target, table) in targets {
+            let Some(table) = table.as_table() else {
+                continue;
+            };
+            read_deps(table, Some(target.as_str().into()), &mut deps)?;
+        }
+    }
+
+    let mut features = BTreeMap::new();
+    if let Some(table) = toml.get("features").and_then(|f| f.as_table()) {
+        for (feature, values) in table {
+            let values = values
+                .as_array()
+                .with_context(|| format!("feature `{feature}` is not a list"))?
+                .iter()
+                .map(|v| {
+                    v.as_str().map(InternedString::new).with_context(|| {
+                        format!("feature `{feature}` has a value that is not a string")
+                    })
+                })
+                .collect::<anyhow::Result<BTreeSet<_>>>()?;
+            features.insert(InternedString::new(feature), values);
+        }
+    }
+
+    let mut version = index_data::Version::new(name.into(), vers, deps, features, links);
+    version.rust_version = rust_version;
+    Ok(version)
+}
+
+fn read_deps(
+    table: &toml::Table,
+    target: Option<InternedString>,
+    deps: &mut Vec<index_data::Dependency>,
+) -> anyhow::Result<()> {
+    for (key, kind) in [
+        ("dependencies", DependencyKind::Normal),
+        ("dev-dependencies", DependencyKind::Dev),
+        ("build-dependencies", DependencyKind::Build),
+    ] {
+        let Some(list) = table.get(key).and_then(|d| d.as_table()) else {
+            continue;
+        };
+        for (name, dep) in list {
+            let dep = read_dep(name, dep, kind).with_context(|| format!("in `{key}.{name}`"))?;
+            deps.push(dep.with_target(target));
+        }
+    }
+    Ok(())
+}
+
+fn read_dep(
+    name: &str,
+    dep: &toml::Value,
+    kind: DependencyKind,
+) -> anyhow::Result<index_data::Dependency> {
+    let table = match dep {
+        toml::Value::String(req) => {
+            return Ok(index_data::Dependency::new(
+                name.into(),
+                name.into(),
+                req.parse()?,
+                Vec::new(),
+                true,
+                kind,
+                false,
+            ))
+        }
+        toml::Value::Table(table) => table,
+        _ => bail!("expected a version requirement or a table"),
+    };
+    let Some(req) = table.get("version").and_then(|v| v.as_str()) else {
+        bail!("only dependencies with a `version` can be resolved against the index");
+    };
+    let package_name = table
+        .get("package")
+        .and_then(|p| p.as_str())
+        .unwrap_or(name);
+    let mut features = table
+        .get("features")
+        .and_then(|f| f.as_array())
+        .map(|f| {
+            f.iter()
+                .filter_map(|f| f.as_str())
+                .map(InternedString::new)
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    features.sort_unstable();
+    let default_features = table
+        .get("default-features")
+        .or_else(|| table.get("default_features"))
+        .and_then(|d| d.as_bool())
+        .unwrap_or(true);
+    let optional = table
+        .get("optional")
+        .and_then(|o| o.as_bool())
+        .unwrap_or(false);
+    Ok(index_data::Dependency::new(
+        name.into(),
+        package_name.into(),
+        req.parse()?,
+        features,
+        default_features,
+        kind,
+        optional,
+    ))
+}
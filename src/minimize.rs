@@ -0,0 +1,306 @@
+//! Shrinks the index data of a failing crate version to a small reproducer.
+//!
+//! Each crate version in the data is removed or simplified in turn,
+//! and the change is kept if resolving the root still fails the same way.
+
+use cargo::{core::Summary, util::interning::InternedString};
+use itertools::Itertools as _;
+use pubgrub::{resolve, PubGrubError};
+
+use crate::{
+    cargo_lock, cargo_resolver, index_data, names::new_bucket, names::Names, pub_lock,
+    read_index::read_test_file, CheckError, Index,
+};
+
+/// How resolving a crate version with PubGrub and cargo disagreed, see [`check`].
+#[derive(Debug)]
+pub enum Failure {
+    /// PubGrub returned an error other than `NoSolution`.
+    PubError(String),
+    /// PubGrub's solution is rejected by `Index::check_cycles` or `Index::check`.
+    InvalidSolution(CheckError),
+    CyclicDisagreement {
+        pub_cyclic: bool,
+        cargo_cyclic: bool,
+    },
+    SuccessDisagreement {
+        pub_ok: bool,
+        cargo_ok: bool,
+    },
+    CargoRejectsPubLock(String),
+    PubRejectsCargoLock(String),
+}
+
+impl std::fmt::Display for Failure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Failure::PubError(e) => write!(f, "pubgrub errored: {e}"),
+            Failure::InvalidSolution(e) => write!(f, "pubgrub check failed: {e}"),
+            Failure::CyclicDisagreement {
+                pub_cyclic,
+                cargo_cyclic,
+            } => write!(
+                f,
+                "cyclic package dependency: pubgrub {pub_cyclic}, cargo {cargo_cyclic}"
+            ),
+            Failure::SuccessDisagreement { pub_ok, cargo_ok } => {
+                write!(f, "success: pubgrub {pub_ok}, cargo {cargo_ok}")
+            }
+            Failure::CargoRejectsPubLock(e) => write!(f, "cargo rejects pubgrub lock: {e}"),
+            Failure::PubRejectsCargoLock(e) => write!(f, "pubgrub rejects cargo lock: {e}"),
+        }
+    }
+}
+
+/// Which failure has to keep happening while minimizing.
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum Predicate {
+    Any,
+    PubError,
+    InvalidSolution,
+    CyclicDisagreement,
+    SuccessDisagreement,
+    CargoRejectsPubLock,
+    PubRejectsCargoLock,
+}
+
+impl Predicate {
+    pub fn matches(&self, failure: &Failure) -> bool {
+        match (self, failure) {
+            (Predicate::Any, _) => true,
+            (Predicate::PubError, Failure::PubError(_)) => true,
+            (Predicate::InvalidSolution, Failure::InvalidSolution(_)) => true,
+            (Predicate::CyclicDisagreement, Failure::CyclicDisagreement { .. }) => true,
+            (Predicate::SuccessDisagreement, Failure::SuccessDisagreement { .. }) => true,
+            (Predicate::CargoRejectsPubLock, Failure::CargoRejectsPubLock(_)) => true,
+            (Predicate::PubRejectsCargoLock, Failure::PubRejectsCargoLock(_)) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Resolves `root` with PubGrub and cargo, and each with the lock of the other,
+/// and returns the first way they disagree.
+pub fn check<'c>(
+    dp: &mut Index<'c>,
+    root: Names<'c>,
+    ver: &semver::Version,
+) -> Result<(), Failure> {
+    dp.reset();
+    let res = resolve(dp, root.clone(), ver.clone());
+    let mut pub_cyclic_package_dependency = false;
+
+    match res.as_ref() {
+        Ok(map) => {
+            match dp.check_cycles(root.clone(), map) {
+                Err(CheckError::Cycle(_)) => pub_cyclic_package_dependency = true,
+                cycles => cycles.map_err(Failure::InvalidSolution)?,
+            }
+            dp.check(root.clone(), map)
+                .map_err(Failure::InvalidSolution)?;
+        }
+
+        Err(PubGrubError::NoSolution(_)) => {}
+        Err(e) => {
+            return Err(Failure::PubError(e.to_string()));
+        }
+    }
+    dp.reset_time();
+    let cargo_out = cargo_resolver::resolve(&root, ver, dp);
+
+    let cyclic_package_dependency = &cargo_out
+        .as_ref()
+        .map_err(|e| e.to_string().starts_with("cyclic package dependency"))
+        == &Err(true);
+
+    if cyclic_package_dependency != pub_cyclic_package_dependency {
+        return Err(Failure::CyclicDisagreement {
+            pub_cyclic: pub_cyclic_package_dependency,
+            cargo_cyclic: cyclic_package_dependency,
+        });
+    }
+
+    if !cyclic_package_dependency && res.is_ok() != cargo_out.is_ok() {
+        return Err(Failure::SuccessDisagreement {
+            pub_ok: res.is_ok(),
+            cargo_ok: cargo_out.is_ok(),
+        });
+    }
+
+    if res.is_ok() {
+        dp.past_result = res.as_ref().map(pub_lock).ok();
+        dp.reset_time();
+        let cargo_check_pub_lock_out = cargo_resolver::resolve(&root, ver, dp);
+
+        let cyclic_package_dependency_pub_lock = &cargo_check_pub_lock_out
+            .as_ref()
+            .map_err(|e| e.to_string().starts_with("cyclic package dependency"))
+            == &Err(true);
+
+        if !cyclic_package_dependency_pub_lock {
+            if let Err(e) = cargo_check_pub_lock_out {
+                return Err(Failure::CargoRejectsPubLock(e.to_string()));
+            }
+        }
+    }
+    if cargo_out.is_ok() {
+        dp.past_result = cargo_out.as_ref().map(cargo_lock).ok();
+        dp.reset_time();
+        let pub_check_cargo_lock_out = resolve(dp, root.clone(), ver.clone());
+
+        if let Err(e) = pub_check_cargo_lock_out {
+            return Err(Failure::PubRejectsCargoLock(e.to_string()));
+        }
+    }
+
+    Ok(())
+}
+
+/// The ways to make one crate version simpler, with a description for the progress output.
+/// `data` is the rest of the index data, used to pick requirements that still match something.
+pub(crate) fn simplifications(
+    removed: &index_data::Version,
+    data: &[index_data::Version],
+) -> Vec<(String, index_data::Version)> {
+    let mut out = vec![];
+    out.extend(
+        removed
+            .clone()
+            .without_features()
+            .map(|v| ("without_features".to_string(), v)),
+    );
+    for f in 0..removed.features_raw.len() {
+        out.extend(
+            removed
+                .clone()
+                .without_a_feature(f)
+                .map(|v| (format!("without_a_feature({f})"), v)),
+        );
+    }
+    for (f, values) in removed.features_raw.values().enumerate() {
+        for j in 0..values.len() {
+            out.extend(
+                removed
+                    .clone()
+                    .without_a_feature_value(f, j)
+                    .map(|v| (format!("without_a_feature_value({f}, {j})"), v)),
+            );
+        }
+    }
+    out.extend(
+        removed
+            .clone()
+            .without_deps()
+            .map(|v| ("without_deps".to_string(), v)),
+    );
+    for d in 0..removed.deps.len() {
+        out.extend(
+            removed
+                .clone()
+                .without_a_dep(d)
+                .map(|v| (format!("without_a_dep({d})"), v)),
+        );
+    }
+    for (d, dep) in removed.deps.iter().enumerate() {
+        // Narrow the requirement to the oldest or the newest version it matches,
+        // as trying each of them takes a resolution per version.
+        let matching = data
+            .iter()
+            .filter(|v| v.name == dep.package_name && dep.req.matches(&v.vers))
+            .map(|v| &*v.vers)
+            .minmax()
+            .into_option();
+        for candidate in matching.into_iter().flat_map(|(a, b)| [a, b]).dedup() {
+            let req: semver::VersionReq = format!("={candidate}").parse().unwrap();
+            out.extend(
+                removed
+                    .clone()
+                    .with_a_req(d, &req)
+                    .map(|v| (format!("with_a_req({d}, {req})"), v)),
+            );
+        }
+    }
+    for (d, dep) in removed.deps.iter().enumerate() {
+        out.extend(
+            removed
+                .clone()
+                .with_a_req(d, &semver::VersionReq::STAR)
+                .map(|v| (format!("with_a_req({d}, *)"), v)),
+        );
+        for c in 0..dep.req.comparators.len() {
+            out.extend(
+                removed
+                    .clone()
+                    .without_a_comparator(d, c)
+                    .map(|v| (format!("without_a_comparator({d}, {c})"), v)),
+            );
+        }
+        out.extend(
+            removed
+                .clone()
+                .without_default_features(d)
+                .map(|v| (format!("without_default_features({d})"), v)),
+        );
+        out.extend(
+            removed
+                .clone()
+                .without_a_rename(d)
+                .map(|v| (format!("without_a_rename({d})"), v)),
+        );
+    }
+    out.retain(|(_, v)| TryInto::<Summary>::try_into(v).is_ok());
+    out
+}
+
+/// Removes and simplifies crate versions from `data` for as long as resolving `name@ver` keeps failing `predicate`.
+///
+/// Returns `None` if it does not fail to begin with.
+pub fn minimize(
+    data: Vec<index_data::Version>,
+    name: InternedString,
+    ver: &semver::Version,
+    predicate: Predicate,
+) -> Option<Vec<index_data::Version>> {
+    let root = new_bucket(name, ver.into(), true);
+    // Only the crate versions looked at by the resolvers are needed to reproduce.
+    let still_fails = |data: &[index_data::Version]| {
+        let crates = read_test_file(data.iter().cloned());
+        let mut dp = Index::new(&crates);
+        match check(&mut dp, root.clone(), ver) {
+            Err(failure) if predicate.matches(&failure) => Some(dp.make_index_ron_data()),
+            _ => None,
+        }
+    };
+
+    let mut data = still_fails(&data)?;
+    let mut offset = 0;
+    'data: loop {
+        for i in 0..data.len() {
+            let len = data.len();
+            let i = (i + offset) % len;
+            let removed = data.swap_remove(i);
+            if let Some(smaller) = still_fails(&data) {
+                data = smaller;
+                offset = i;
+                println!("Failed on removing {i} of {len}");
+                continue 'data;
+            }
+            for (what, simpler) in simplifications(&removed, &data) {
+                data.push(simpler);
+                if let Some(smaller) = still_fails(&data) {
+                    data = smaller;
+                    offset = i;
+                    println!("Failed on {what} {i} of {len}");
+                    continue 'data;
+                }
+                data.pop();
+            }
+            // Put it back where it was.
+            data.push(removed);
+            let last = data.len() - 1;
+            data.swap(i, last);
+        }
+        break;
+    }
+    Some(data)
+}
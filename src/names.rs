@@ -48,6 +48,10 @@ impl serde::Serialize for FeatureNamespace<'_> {
 
 #[derive(Clone, Eq, PartialEq, Hash)]
 pub enum Names<'c> {
+    /// A root that is not in the index, like a local `Cargo.toml`, see `Index::with_root`.
+    Root(InternedString),
+    /// A synthetic root that depends on each root of a workspace, see `Index::with_workspace`.
+    Workspace(InternedString),
     Bucket(InternedString, SemverCompatibility, bool),
     BucketFeatures(InternedString, SemverCompatibility, FeatureNamespace<'c>),
     BucketDefaultFeatures(InternedString, SemverCompatibility),
@@ -71,6 +75,9 @@ pub enum Names<'c> {
         SemverCompatibility,
     ),
     Links(InternedString),
+    /// One of the cyclic package dependencies that are forbidden, see `Index::forbid_cycle`.
+    /// Named after the first crate in the cycle, and numbered in the order they were found.
+    Cycle(InternedString, usize),
 }
 
 pub fn new_bucket<'c>(
@@ -96,24 +103,30 @@ impl<'c> Ord for Names<'c> {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
         {
             match &self {
-                Names::Bucket(c, _, _)
+                Names::Root(c)
+                | Names::Workspace(c)
+                | Names::Bucket(c, _, _)
                 | Names::BucketFeatures(c, _, _)
                 | Names::BucketDefaultFeatures(c, _)
                 | Names::Wide(c, _, _, _)
                 | Names::WideFeatures(c, _, _, _, _)
                 | Names::WideDefaultFeatures(c, _, _, _)
-                | Names::Links(c) => *c,
+                | Names::Links(c)
+                | Names::Cycle(c, _) => *c,
             }
         }
         .cmp({
             match &other {
-                Names::Bucket(c, _, _)
+                Names::Root(c)
+                | Names::Workspace(c)
+                | Names::Bucket(c, _, _)
                 | Names::BucketFeatures(c, _, _)
                 | Names::BucketDefaultFeatures(c, _)
                 | Names::Wide(c, _, _, _)
                 | Names::WideFeatures(c, _, _, _, _)
                 | Names::WideDefaultFeatures(c, _, _, _)
-                | Names::Links(c) => c,
+                | Names::Links(c)
+                | Names::Cycle(c, _) => c,
             }
         })
         .then_with(|| self.to_string().cmp(&other.to_string()))
@@ -128,17 +141,19 @@ impl<'c> PartialOrd for Names<'c> {
 
 impl<'c> Names<'c> {
     pub fn is_real(&self) -> bool {
-        matches!(self, &Self::Bucket(..))
+        matches!(self, &Self::Bucket(..) | &Self::Root(_))
     }
     pub fn crate_(&self) -> InternedString {
         match self {
-            Names::Bucket(c, _, _)
+            Names::Root(c)
+            | Names::Workspace(c)
+            | Names::Bucket(c, _, _)
             | Names::BucketFeatures(c, _, _)
             | Names::BucketDefaultFeatures(c, _)
             | Names::Wide(c, _, _, _)
             | Names::WideFeatures(c, _, _, _, _)
             | Names::WideDefaultFeatures(c, _, _, _) => *c,
-            Names::Links(_) => panic!(),
+            Names::Links(_) | Names::Cycle(_, _) => panic!(),
         }
     }
     pub fn with_default_features(&self) -> Self {
@@ -146,7 +161,7 @@ impl<'c> Names<'c> {
         match self {
             Bucket(a, b, _) | BucketFeatures(a, b, _) => BucketDefaultFeatures(*a, *b),
             Wide(a, b, c, d) | WideFeatures(a, b, c, d, _) => WideDefaultFeatures(*a, b, *c, *d),
-            Links(_) => panic!(),
+            Root(_) | Workspace(_) | Links(_) | Cycle(_, _) => panic!(),
             s @ BucketDefaultFeatures(_, _) | s @ WideDefaultFeatures(_, _, _, _) => s.clone(),
         }
     }
@@ -159,7 +174,7 @@ impl<'c> Names<'c> {
             Wide(a, b, c, d) | WideFeatures(a, b, c, d, _) | WideDefaultFeatures(a, b, c, d) => {
                 WideFeatures(*a, b, *c, *d, feat)
             }
-            Links(_) => panic!(),
+            Root(_) | Workspace(_) | Links(_) | Cycle(_, _) => panic!(),
         }
     }
 }
@@ -167,6 +182,14 @@ impl<'c> Names<'c> {
 impl<'c> std::fmt::Display for Names<'c> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
+            Names::Root(n) => {
+                f.write_str("Root:")?;
+                f.write_str(n)
+            }
+            Names::Workspace(n) => {
+                f.write_str("Workspace:")?;
+                f.write_str(n)
+            }
             Names::Bucket(n, m, a) => {
                 f.write_str("Bucket:")?;
                 f.write_str(n)?;
@@ -253,6 +276,12 @@ impl<'c> std::fmt::Display for Names<'c> {
                 f.write_str("Links:")?;
                 f.write_str(name)
             }
+            Names::Cycle(name, id) => {
+                f.write_str("Cycle:")?;
+                f.write_str(name)?;
+                f.write_str("#")?;
+                f.write_str(&id.to_string())
+            }
         }
     }
 }
@@ -1,15 +1,17 @@
 use std::time::Instant;
 
-use cargo::{core::Summary, util::interning::InternedString};
+use cargo::util::interning::InternedString;
 use crates_index::GitIndex;
 use rayon::iter::ParallelIterator;
 
-use crate::{index_data, IndexMapLookup};
+use crate::{cargo_resolver::summary_for_target, index_data, target::Target, IndexMapLookup};
 
+/// Reads the crate versions that pass the filters, with cargo summaries made for `target`, see `Index::with_target`.
 pub fn read_index(
     index: &GitIndex,
     create_filter: impl Fn(&str) -> bool + Sync + 'static,
     version_filter: impl Fn(&index_data::Version) -> bool + Sync + 'static,
+    target: Option<&Target>,
 ) -> IndexMapLookup {
     println!("Start reading index");
     let start = Instant::now();
@@ -22,10 +24,18 @@ pub fn read_index(
             let ver_lookup = crt
                 .versions()
                 .iter()
-                .filter_map(|v| TryInto::<index_data::Version>::try_into(v).ok())
-                .filter(|v| version_filter(v))
-                .filter_map(|v| {
-                    let s: Summary = (&v).try_into().ok()?;
+                .filter_map(|v| Some((TryInto::<index_data::Version>::try_into(v).ok()?, v)))
+                .filter(|(v, _)| version_filter(v))
+                .filter_map(|(v, index_ver)| {
+                    let mut s = summary_for_target(&v, target).ok()?;
+                    // For the `Cargo.lock` files, see `lockfile`.
+                    s.set_checksum(
+                        index_ver
+                            .checksum()
+                            .iter()
+                            .map(|b| format!("{b:02x}"))
+                            .collect(),
+                    );
 
                     Some(((*v.vers).clone(), (v, s)))
                 })
@@ -40,7 +50,6 @@ pub fn read_index(
     crates
 }
 
-#[cfg(test)]
 pub fn read_test_file(iter: impl IntoIterator<Item = index_data::Version>) -> IndexMapLookup {
     let mut deps = IndexMapLookup::default();
 
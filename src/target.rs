@@ -0,0 +1,53 @@
+//! Decides which platform specific dependencies, like `[target.'cfg(windows)'.dependencies]`, are used.
+//!
+//! Cargo resolves the dependencies of every platform, so that `Cargo.lock` works everywhere.
+//! Resolving for one platform is what a build on that platform needs, and can be a lot less work.
+
+use std::{process::Command, str::FromStr};
+
+use anyhow::{bail, Context as _};
+use cargo_platform::{Cfg, Platform};
+
+/// A platform to resolve for, see `Index::with_target`.
+#[derive(Debug)]
+pub struct Target {
+    triple: String,
+    cfg: Vec<Cfg>,
+}
+
+impl Target {
+    /// Asks `rustc` for the cfg values of `triple`, as cargo does.
+    pub fn from_rustc(triple: &str) -> anyhow::Result<Self> {
+        let out = Command::new("rustc")
+            .args(["--print", "cfg", "--target", triple])
+            .output()
+            .context("running rustc")?;
+        if !out.status.success() {
+            bail!(
+                "rustc --print cfg --target {triple} failed: {}",
+                String::from_utf8_lossy(&out.stderr)
+            );
+        }
+        Self::from_cfg(triple, &String::from_utf8(out.stdout)?)
+    }
+
+    /// The platform `triple`, with the cfg values in `cfg` in the format of `rustc --print cfg`.
+    pub fn from_cfg(triple: &str, cfg: &str) -> anyhow::Result<Self> {
+        let cfg = cfg.lines().map(Cfg::from_str).collect::<Result<_, _>>()?;
+        Ok(Self {
+            triple: triple.to_string(),
+            cfg,
+        })
+    }
+
+    pub fn triple(&self) -> &str {
+        &self.triple
+    }
+
+    /// Whether a dependency on `target`, a target triple or a `cfg(..)` expression, is used on this platform.
+    ///
+    /// A `target` that does not parse is treated as used, like a dependency without one.
+    pub fn matches(&self, target: &str) -> bool {
+        Platform::from_str(target).map_or(true, |p| p.matches(&self.triple, &self.cfg))
+    }
+}
@@ -1,6 +1,7 @@
 use std::path::Path;
 
 use super::*;
+use crate::minimize::{check, minimize, Predicate};
 
 fn case_from_file_name(file_name: &str) -> (&str, semver::Version) {
     let (name, rest) = file_name.split_once("@").unwrap();
@@ -14,102 +15,58 @@ fn crates_data_from_file<P: AsRef<Path>>(path: P) -> IndexMapLookup {
     read_test_file(data)
 }
 
-#[must_use]
-fn check<'c>(dp: &mut Index<'c>, root: Names<'c>, ver: &semver::Version) -> bool {
-    dp.reset();
-    let res = resolve(dp, root.clone(), ver.clone());
-    let pub_cyclic_package_dependency = if let Ok(map) = res.as_ref() {
-        dp.check_cycles(root.clone(), map)
-    } else {
-        false
-    };
-
-    match res.as_ref() {
-        Ok(map) => {
-            if !dp.check(root.clone(), &map) {
-                return false;
-            }
-        }
-
-        Err(PubGrubError::NoSolution(_derivation)) => {
-            // eprintln!("{}", DefaultStringReporter::report(&derivation));
-        }
-        Err(_e) => {
-            return false;
-        }
-    }
-    dp.reset_time();
-    let cargo_out = cargo_resolver::resolve(root.crate_().into(), &ver, dp);
-
-    let cyclic_package_dependency = &cargo_out
-        .as_ref()
-        .map_err(|e| e.to_string().starts_with("cyclic package dependency"))
-        == &Err(true);
-
-    if cyclic_package_dependency != pub_cyclic_package_dependency {
-        return false;
-    }
+/// The data of a file in `out/index_ron`, with the root and version it is named after.
+fn root_from_file(file_name: &str) -> (IndexMapLookup, Names<'static>, semver::Version) {
+    let crates = crates_data_from_file(format!("out/index_ron/{file_name}"));
+    let (name, ver) = case_from_file_name(file_name);
+    let root = new_bucket(InternedString::new(name), (&ver).into(), true);
+    (crates, root, ver)
+}
 
-    if !cyclic_package_dependency && res.is_ok() != cargo_out.is_ok() {
-        return false;
+/// Like `crates_data_from_file`, but with the cargo summaries made for `target`, as `read_index` does.
+fn crates_data_for_target<P: AsRef<Path>>(path: P, target: &Target) -> IndexMapLookup {
+    let data = std::fs::read_to_string(path).unwrap();
+    let data: Vec<index_data::Version> = ron::de::from_str(&data).unwrap();
+    let mut crates = IndexMapLookup::default();
+    for v in data {
+        let s = cargo_resolver::summary_for_target(&v, Some(target)).unwrap();
+        crates
+            .entry(v.name)
+            .or_default()
+            .insert((*v.vers).clone(), (v, s));
     }
+    crates
+}
 
-    if res.is_ok() {
-        dp.past_result = res
-            .as_ref()
-            .map(|map| {
-                let mut results: HashMap<
-                    InternedString,
-                    BTreeSet<semver::Version>,
-                    rustc_hash::FxBuildHasher,
-                > = HashMap::default();
-                for (k, v) in map.iter() {
-                    if k.is_real() {
-                        results.entry(k.crate_()).or_default().insert(v.clone());
-                    }
-                }
-                results
-            })
-            .ok();
-        dp.reset_time();
-        let cargo_check_pub_lock_out = cargo_resolver::resolve(root.crate_().into(), &ver, dp);
-
-        let cyclic_package_dependency_pub_lock = &cargo_check_pub_lock_out
-            .as_ref()
-            .map_err(|e| e.to_string().starts_with("cyclic package dependency"))
-            == &Err(true);
-
-        if !cyclic_package_dependency_pub_lock && !cargo_check_pub_lock_out.is_ok() {
-            return false;
-        }
-    }
-    if cargo_out.is_ok() {
-        dp.past_result = cargo_out
-            .as_ref()
-            .map(|map| {
-                let mut results: HashMap<
-                    InternedString,
-                    BTreeSet<semver::Version>,
-                    rustc_hash::FxBuildHasher,
-                > = HashMap::default();
-                for v in map.iter() {
-                    results
-                        .entry(v.name())
-                        .or_default()
-                        .insert(v.version().clone());
-                }
-                results
-            })
-            .ok();
-        dp.reset_time();
-        let pub_check_cargo_lock_out = resolve(dp, root.clone(), ver.clone());
-
-        if !pub_check_cargo_lock_out.is_ok() {
-            return false;
-        }
-    }
+/// The `name@ver` of the crates PubGrub and cargo select for `root@ver`.
+fn solutions<'c>(
+    dp: &mut Index<'c>,
+    root: Names<'c>,
+    ver: &semver::Version,
+) -> (Vec<String>, Vec<String>) {
+    let (pub_set, cargo_set) = try_solutions(dp, root, ver);
+    (pub_set.unwrap(), cargo_set.unwrap())
+}
 
-    true
+/// Like `solutions`, but `None` for a resolver that fails.
+fn try_solutions<'c>(
+    dp: &mut Index<'c>,
+    root: Names<'c>,
+    ver: &semver::Version,
+) -> (Option<Vec<String>>, Option<Vec<String>>) {
+    dp.reset();
+    let pub_map = resolve(dp, root.clone(), ver.clone()).ok();
+    dp.reset_time();
+    let cargo_map = cargo_resolver::resolve(&root, ver, dp).ok();
+    let to_strings = |set: BTreeSet<(InternedString, semver::Version)>| {
+        set.into_iter()
+            .map(|(n, v)| format!("{n}@{v}"))
+            .collect_vec()
+    };
+    (
+        pub_map.map(|m| to_strings(pub_solution(&m))),
+        cargo_map.map(|m| to_strings(cargo_solution(&m))),
+    )
 }
 
 #[test]
@@ -151,9 +108,9 @@ fn named_from_files_pass_tests() {
         let crates = crates_data_from_file(&case);
         let mut dp = Index::new(&crates);
         let root = new_bucket(name, (&ver).into(), true);
-        if !check(&mut dp, root, &ver) {
+        if let Err(e) = check(&mut dp, root, &ver) {
             dp.make_index_ron_file();
-            faild.push(file_name.to_string());
+            faild.push(format!("{file_name}: {e}"));
         };
         dp.make_pubgrub_ron_file();
         eprintln!(" in {}s", start_time.elapsed().as_secs());
@@ -170,90 +127,12 @@ fn named_from_files_pass_without_vers() {
         let (name, ver) = case_from_file_name(&file_name);
         let name = InternedString::new(name);
         eprintln!("Running: {name} @ {ver}");
-        let root = new_bucket(name, (&ver).into(), true);
         let start_time = std::time::Instant::now();
         let data = std::fs::read_to_string(&case).unwrap();
-        let mut data: Vec<index_data::Version> = ron::de::from_str(&data).unwrap();
-        let mut offset = 0;
-        'data: loop {
-            for i in 0..data.len() {
-                let len = data.len();
-                let i = (i + offset) % len;
-                let removed = data.swap_remove(i);
-                let crates = read_test_file(data.iter().cloned());
-                let mut dp = Index::new(&crates);
-                if !check(&mut dp, root.clone(), &ver) {
-                    data = dp.make_index_ron_data();
-                    offset = i;
-                    println!("Failed on removing {i} of {len}");
-                    continue 'data;
-                };
-                if let Some(without_features) = removed.clone().without_features() {
-                    data.push(without_features);
-                    let crates = read_test_file(data.iter().cloned());
-                    let mut dp = Index::new(&crates);
-                    if !check(&mut dp, root.clone(), &ver) {
-                        data = dp.make_index_ron_data();
-                        offset = i;
-                        println!("Failed on without_features {i} of {len}");
-                        continue 'data;
-                    };
-                    data.pop();
-                }
-                for f in 0..removed.features.len() {
-                    if let Some(without_features) = removed.clone().without_a_feature(f) {
-                        if TryInto::<Summary>::try_into(&without_features).is_ok() {
-                            data.push(without_features);
-                            let crates = read_test_file(data.iter().cloned());
-                            let mut dp = Index::new(&crates);
-                            if !check(&mut dp, root.clone(), &ver) {
-                                data = dp.make_index_ron_data();
-                                offset = i;
-                                println!("Failed on without_a_feature({f}) {i} of {len}");
-                                continue 'data;
-                            };
-                            data.pop();
-                        }
-                    }
-                }
-                if let Some(without_deps) = removed.clone().without_deps() {
-                    if TryInto::<Summary>::try_into(&without_deps).is_ok() {
-                        data.push(without_deps);
-                        let crates = read_test_file(data.iter().cloned());
-                        let mut dp = Index::new(&crates);
-                        if !check(&mut dp, root.clone(), &ver) {
-                            data = dp.make_index_ron_data();
-                            offset = i;
-                            println!("Failed on without_deps {i} of {len}");
-                            continue 'data;
-                        };
-                        data.pop();
-                    }
-                }
-                for d in 0..removed.deps.len() {
-                    if let Some(without_deps) = removed.clone().without_a_dep(d) {
-                        if TryInto::<Summary>::try_into(&without_deps).is_ok() {
-                            data.push(without_deps);
-                            let crates = read_test_file(data.iter().cloned());
-                            let mut dp = Index::new(&crates);
-                            if !check(&mut dp, root.clone(), &ver) {
-                                data = dp.make_index_ron_data();
-                                offset = i;
-                                println!("Failed on without_a_dep({d}) {i} of {len}");
-                                continue 'data;
-                            };
-                            data.pop();
-                        }
-                    }
-                }
-            }
-            break;
+        let data: Vec<index_data::Version> = ron::de::from_str(&data).unwrap();
+        if let Some(data) = minimize(data, name, &ver, Predicate::Any) {
+            write_index_ron_file(&format!("out/index_ron/{name}@{ver}.ron"), &data);
         }
-        let crates = read_test_file(data);
-        let mut dp = Index::new(&crates);
-        if !check(&mut dp, root, &ver) {
-            dp.make_index_ron_file();
-        };
 
         eprintln!(" in {}s", start_time.elapsed().as_secs());
     }
@@ -273,9 +152,9 @@ fn all_vers_in_files_pass_tests() {
         for (name, vers) in &crates {
             for ver in vers.keys() {
                 let root = new_bucket(*name, ver.into(), true);
-                if !check(&mut dp, root, ver) {
+                if let Err(e) = check(&mut dp, root, ver) {
                     dp.make_index_ron_file();
-                    faild.push(format!("{file_name}:{name}@{ver}"));
+                    faild.push(format!("{file_name}:{name}@{ver}: {e}"));
                 };
             }
         }
@@ -283,3 +162,665 @@ fn all_vers_in_files_pass_tests() {
     }
     assert_eq!(faild.as_slice(), &Vec::<String>::new());
 }
+
+const LINUX_CFG: &str = r#"debug_assertions
+panic="unwind"
+target_arch="x86_64"
+target_endian="little"
+target_env="gnu"
+target_family="unix"
+target_os="linux"
+target_pointer_width="64"
+target_vendor="unknown"
+unix
+"#;
+
+#[test]
+fn target_matches() {
+    let target = Target::from_cfg("x86_64-unknown-linux-gnu", LINUX_CFG).unwrap();
+    assert!(target.matches("x86_64-unknown-linux-gnu"));
+    assert!(!target.matches("x86_64-pc-windows-msvc"));
+    assert!(target.matches("cfg(unix)"));
+    assert!(!target.matches("cfg(windows)"));
+    assert!(target.matches(r#"cfg(target_os = "linux")"#));
+    assert!(!target.matches(r#"cfg(target_os = "macos")"#));
+    assert!(target.matches(r#"cfg(all(unix, target_arch = "x86_64"))"#));
+    assert!(!target.matches(r#"cfg(all(unix, target_arch = "aarch64"))"#));
+    assert!(target.matches(r#"cfg(any(windows, target_os = "linux"))"#));
+    assert!(!target.matches(r#"cfg(any(windows, target_os = "macos"))"#));
+    assert!(target.matches("cfg(not(windows))"));
+    assert!(!target.matches("cfg(not(unix))"));
+    // Like a dependency without a target, one that does not parse is used.
+    assert!(target.matches("cfg(unix"));
+    assert!(target.matches("cfg(target_os = )"));
+    assert!(target.matches("not a target"));
+}
+
+#[test]
+fn target_specific_dependency() {
+    let file_name = "test_29_target_specific_dependency@0.0.1.ron";
+    let (crates, root, ver) = root_from_file(file_name);
+    let mut dp = Index::new(&crates);
+    let (pub_set, cargo_set) = solutions(&mut dp, root.clone(), &ver);
+    assert_eq!(pub_set, cargo_set);
+    assert_eq!(
+        pub_set,
+        [
+            "a@1.0.0",
+            "a@2.0.0",
+            "test_29_target_specific_dependency@0.0.1"
+        ]
+    );
+
+    let target = Target::from_cfg("x86_64-unknown-linux-gnu", LINUX_CFG).unwrap();
+    let crates = crates_data_for_target(format!("out/index_ron/{file_name}"), &target);
+    let mut dp = Index::new(&crates).with_target(Some(&target));
+    let (pub_set, cargo_set) = solutions(&mut dp, root, &ver);
+    assert_eq!(pub_set, cargo_set);
+    assert_eq!(
+        pub_set,
+        ["a@2.0.0", "test_29_target_specific_dependency@0.0.1"]
+    );
+}
+
+#[test]
+fn lockfile_round_trip() {
+    let (crates, root, ver) = root_from_file("test_01_renamed_package@0.0.1.ron");
+    let mut dp = Index::new(&crates);
+    let pub_map = resolve(&mut dp, root, ver).unwrap();
+    let text = lockfile::pub_lockfile(&dp, &pub_map);
+    assert_eq!(lockfile::parse_lock(&text).unwrap(), pub_lock(&pub_map));
+}
+
+#[test]
+fn lockfile_pub_matches_cargo() {
+    let (crates, root, ver) = root_from_file("test_01_renamed_package@0.0.1.ron");
+    let mut dp = Index::new(&crates);
+    let pub_map = resolve(&mut dp, root.clone(), ver.clone()).unwrap();
+    let cargo_map = cargo_resolver::resolve(&root, &ver, &mut dp).unwrap();
+    assert_eq!(
+        lockfile::pub_lockfile(&dp, &pub_map),
+        lockfile::cargo_lockfile(&cargo_map)
+    );
+}
+
+#[test]
+fn manifest_dependencies() {
+    let manifest = manifest::parse_manifest(
+        r#"
+[package]
+name = "root"
+version = "0.1.0"
+
+[dependencies]
+a = "1.0"
+b = { version = "2", package = "c", default-features = false, features = ["y", "x"] }
+d = { version = "3", default_features = false, optional = true }
+
+[target.'cfg(unix)'.dependencies]
+e = "4"
+"#,
+    )
+    .unwrap();
+    assert_eq!(manifest.name.as_str(), "root");
+    assert_eq!(*manifest.vers, "0.1.0".parse().unwrap());
+    let deps = manifest
+        .deps
+        .iter()
+        .map(|d| (d.name, d))
+        .collect::<BTreeMap<_, _>>();
+
+    let a = deps["a"];
+    assert_eq!(a.package_name.as_str(), "a");
+    assert_eq!(*a.req, "^1.0".parse().unwrap());
+    assert!(a.default_features);
+    assert!(!a.optional);
+    assert_eq!(a.target, None);
+
+    let b = deps["b"];
+    assert_eq!(b.package_name.as_str(), "c");
+    assert_eq!(*b.req, "^2".parse().unwrap());
+    assert!(!b.default_features);
+    assert_eq!(
+        b.features.iter().map(|f| f.as_str()).collect_vec(),
+        ["x", "y"]
+    );
+
+    let d = deps["d"];
+    assert!(!d.default_features);
+    assert!(d.optional);
+
+    assert_eq!(deps["e"].target, Some("cfg(unix)".into()));
+}
+
+#[test]
+fn manifest_rejects_dependencies_not_in_the_index() {
+    for dep in [
+        r#"a = { path = "../a" }"#,
+        r#"a = { git = "https://github.com/rust-lang/a" }"#,
+        "a = { workspace = true }",
+    ] {
+        let text = format!("[package]\nname = \"root\"\n\n[dependencies]\n{dep}\n");
+        assert!(manifest::parse_manifest(&text).is_err(), "{dep}");
+    }
+}
+
+#[test]
+fn manifest_root_with_both_resolvers() {
+    let manifest = manifest::parse_manifest(
+        r#"
+[package]
+name = "root"
+version = "0.1.0"
+
+[dependencies]
+a = "0.0.1"
+
+[dev-dependencies]
+b = "0.0.1"
+"#,
+    )
+    .unwrap();
+    let crates = crates_data_from_file("out/index_ron/test_01_renamed_package@0.0.1.ron");
+    let ver: semver::Version = "0.1.0".parse().unwrap();
+    let mut dp = Index::new(&crates).with_root(&manifest);
+    let root = Names::Root(manifest.name);
+    let (pub_set, cargo_set) = solutions(&mut dp, root, &ver);
+    assert_eq!(pub_set, cargo_set);
+    assert_eq!(pub_set, ["a@0.0.1", "b@0.0.1", "root@0.1.0"]);
+
+    // The fixture of a `Names::Root` has its manifest, to resolve it again as a `Names::Bucket`.
+    let replay = read_test_file(dp.make_index_ron_data());
+    let mut dp = Index::new(&replay);
+    let root = new_bucket(manifest.name, (&ver).into(), true);
+    let (replay_set, _) = solutions(&mut dp, root, &ver);
+    assert_eq!(replay_set, pub_set);
+}
+
+#[test]
+fn workspace_with_both_resolvers() {
+    let member_a = manifest::parse_manifest(
+        r#"
+[package]
+name = "member_a"
+version = "0.1.0"
+
+[dependencies]
+a = "0.0.1"
+"#,
+    )
+    .unwrap();
+    let member_b = manifest::parse_manifest(
+        r#"
+[package]
+name = "member_b"
+version = "0.2.0"
+
+[dev-dependencies]
+b = "0.0.1"
+"#,
+    )
+    .unwrap();
+    let crates = crates_data_from_file("out/index_ron/test_01_renamed_package@0.0.1.ron");
+    let members = [&member_a, &member_b]
+        .iter()
+        .map(|m| (Names::Root(m.name), (*m.vers).clone()))
+        .collect();
+    let mut dp = Index::new(&crates)
+        .with_root(&member_a)
+        .with_root(&member_b)
+        .with_workspace(members);
+    let root = Names::Workspace(InternedString::new("workspace"));
+    let (pub_set, cargo_set) = solutions(&mut dp, root, &semver::Version::new(0, 0, 0));
+    assert_eq!(pub_set, cargo_set);
+    assert_eq!(
+        pub_set,
+        ["a@0.0.1", "b@0.0.1", "member_a@0.1.0", "member_b@0.2.0"]
+    );
+}
+
+#[test]
+fn check_missing_transitive_feature() {
+    let (crates, root, ver) = root_from_file("test_30_missing_transitive_feature@0.0.1.ron");
+    let mut dp = Index::new(&crates);
+    let mut pub_map = resolve(&mut dp, root.clone(), ver.clone()).unwrap();
+    assert_eq!(dp.check(root.clone(), &pub_map), Ok(()));
+
+    // Feature `f` of `a` activates `g`, so a solution without it is not valid.
+    let a = InternedString::new("a");
+    let g = new_bucket(a, (&ver).into(), false).with_features(FeatureNamespace::Feat("g"));
+    assert!(pub_map.remove(&g).is_some());
+    assert_eq!(
+        dp.check(root, &pub_map),
+        Err(CheckError::UnsatisfiedFeature {
+            name: a,
+            ver,
+            feature: "f".into(),
+            item: "g".into(),
+        })
+    );
+}
+
+#[test]
+fn forbid_cycles_two_rounds() {
+    let (crates, root, ver) = root_from_file("test_31_forbid_two_cycles@0.0.1.ron");
+    let mut dp = Index::new(&crates).with_forbid_cycles(true);
+    let checked = resolve_and_check(&mut dp, root, &ver);
+    assert_eq!(checked.forbidden_cycles.len(), 2);
+    assert_eq!(checked.cycle, None);
+    assert_eq!(checked.check_error, None);
+    let solution = pub_solution(&checked.res.unwrap())
+        .into_iter()
+        .map(|(n, v)| format!("{n}@{v}"))
+        .collect_vec();
+    assert_eq!(solution, ["a@0.1.0", "test_31_forbid_two_cycles@0.0.1"]);
+}
+
+#[test]
+fn forbid_cycles_without_a_cycle_free_solution() {
+    let (crates, root, ver) = root_from_file("test_32_only_cyclic_solutions@0.0.1.ron");
+    let mut dp = Index::new(&crates).with_forbid_cycles(true);
+    let checked = resolve_and_check(&mut dp, root, &ver);
+    assert_eq!(checked.forbidden_cycles.len(), 1);
+    assert!(matches!(checked.res, Err(PubGrubError::NoSolution(_))));
+}
+
+#[test]
+fn forbid_cycles_up_to_the_limit() {
+    let (crates, root, ver) = root_from_file("test_35_more_cycles_than_forbidden@0.0.1.ron");
+    let mut dp = Index::new(&crates).with_forbid_cycles(true);
+    let checked = resolve_and_check(&mut dp, root, &ver);
+    // Each of the 17 versions of `a` is in a cycle with `b`, one more than are forbidden.
+    assert_eq!(checked.forbidden_cycles.len(), MAX_FORBIDDEN_CYCLES);
+    assert!(checked.res.is_ok());
+    assert!(checked.cycle.is_some());
+}
+
+/// The `explain::explain` of resolving the root of a file in `out/index_ron`, which has no solution.
+fn explain_file(file_name: &str) -> String {
+    let (crates, root, ver) = root_from_file(file_name);
+    let mut dp = Index::new(&crates);
+    let Err(PubGrubError::NoSolution(derivation)) = resolve(&mut dp, root, ver) else {
+        panic!("{file_name} has a solution");
+    };
+    explain::explain(&derivation)
+}
+
+#[test]
+fn explain_links_conflict() {
+    let explanation = explain_file("test_15_duplicate_sys_crate@0.0.1.ron");
+    assert!(
+        explanation.contains(r#"links = "wasm_bindgen" conflict: only one of "#),
+        "{explanation}"
+    );
+    assert!(
+        explanation.contains("root crate `test_15_duplicate_sys_crate` 0.0.1"),
+        "{explanation}"
+    );
+}
+
+#[test]
+fn explain_missing_optional_dependency() {
+    let explanation = explain_file("test_16_missing_optional_dependency@0.0.1.ron");
+    assert!(
+        explanation.contains("no versions of crate `c`"),
+        "{explanation}"
+    );
+}
+
+#[test]
+fn explain_dependency_on_another_major_of_itself() {
+    let explanation = explain_file("test_36_depends_on_other_major_of_itself@0.0.1.ron");
+    assert!(
+        explanation
+            .lines()
+            .any(|l| l.starts_with("crate `a` 0.2.x") && l.contains(" depends on crate `a` ")),
+        "{explanation}"
+    );
+    assert!(
+        explanation.contains("no versions of crate `c`"),
+        "{explanation}"
+    );
+}
+
+#[test]
+fn minimize_simplifications() {
+    let data = std::fs::read_to_string("out/index_ron/test_01_renamed_package@0.0.1.ron").unwrap();
+    let data: Vec<index_data::Version> = ron::de::from_str(&data).unwrap();
+    let (a, mut rest): (Vec<_>, Vec<_>) = data.into_iter().partition(|v| v.name.as_str() == "a");
+    for ver in ["0.0.2", "0.0.3"] {
+        let b = index_data::Version::new(
+            InternedString::new("b"),
+            ver.parse().unwrap(),
+            vec![],
+            BTreeMap::new(),
+            None,
+        );
+        rest.push(b);
+    }
+    let simpler = minimize::simplifications(&a[0], &rest);
+
+    // Only the oldest and newest matching versions are tried.
+    let reqs = simpler
+        .iter()
+        .map(|(what, _)| what.as_str())
+        .filter(|what| what.starts_with("with_a_req(0, ="))
+        .collect_vec();
+    assert_eq!(reqs, ["with_a_req(0, =0.0.1)", "with_a_req(0, =0.0.3)"]);
+
+    // The `default` feature that activated `b_package` now activates `b`.
+    let (_, unrenamed) = simpler
+        .iter()
+        .find(|(what, _)| what == "without_a_rename(0)")
+        .unwrap();
+    assert_eq!(unrenamed.deps.get("b").len(), 1);
+    let default = unrenamed.features_raw.get("default").unwrap();
+    assert_eq!(default.iter().map(|f| f.as_str()).collect_vec(), ["b"]);
+
+    // And resolves to the same crate versions as before.
+    rest.push(unrenamed.clone());
+    let crates = read_test_file(rest);
+    let ver: semver::Version = "0.0.1".parse().unwrap();
+    let root = new_bucket(
+        InternedString::new("test_01_renamed_package"),
+        (&ver).into(),
+        true,
+    );
+    let (pub_set, cargo_set) = solutions(&mut Index::new(&crates), root, &ver);
+    assert_eq!(pub_set, cargo_set);
+    assert_eq!(
+        pub_set,
+        ["a@0.0.1", "b@0.0.3", "test_01_renamed_package@0.0.1"]
+    );
+}
+
+#[test]
+fn rust_version_prefers_compatible_versions() {
+    let (crates, root, ver) = root_from_file("test_33_rust_version@0.0.1.ron");
+
+    let mut dp = Index::new(&crates);
+    let (pub_set, cargo_set) = solutions(&mut dp, root.clone(), &ver);
+    assert_eq!(pub_set, cargo_set);
+    assert_eq!(pub_set, ["a@1.1.0", "test_33_rust_version@0.0.1"]);
+
+    // `a@1.1.0` needs Rust 1.80.
+    let mut dp = Index::new(&crates).with_rust_version(Some("1.70.0".parse().unwrap()));
+    let (pub_set, cargo_set) = solutions(&mut dp, root.clone(), &ver);
+    assert_eq!(pub_set, cargo_set);
+    assert_eq!(pub_set, ["a@1.0.0", "test_33_rust_version@0.0.1"]);
+
+    // Without a compatible version, the newest is still used.
+    let mut dp = Index::new(&crates).with_rust_version(Some("1.50.0".parse().unwrap()));
+    let (pub_set, cargo_set) = solutions(&mut dp, root, &ver);
+    assert_eq!(pub_set, cargo_set);
+    assert_eq!(pub_set, ["a@1.1.0", "test_33_rust_version@0.0.1"]);
+}
+
+#[test]
+fn minimal_versions_order() {
+    let (crates, root, ver) = root_from_file("test_34_direct_and_transitive_versions@0.0.1.ron");
+
+    let mut dp = Index::new(&crates);
+    let (pub_set, cargo_set) = solutions(&mut dp, root.clone(), &ver);
+    assert_eq!(pub_set, cargo_set);
+    assert_eq!(
+        pub_set,
+        [
+            "a@1.1.0",
+            "b@1.1.0",
+            "test_34_direct_and_transitive_versions@0.0.1"
+        ]
+    );
+
+    let mut dp = Index::new(&crates).with_versions_order(VersionsOrder::Minimal);
+    let (pub_set, cargo_set) = solutions(&mut dp, root, &ver);
+    assert_eq!(pub_set, cargo_set);
+    assert_eq!(
+        pub_set,
+        [
+            "a@1.0.0",
+            "b@1.0.0",
+            "test_34_direct_and_transitive_versions@0.0.1"
+        ]
+    );
+}
+
+#[test]
+fn direct_minimal_versions_order() {
+    let (crates, root, ver) = root_from_file("test_34_direct_and_transitive_versions@0.0.1.ron");
+
+    // Only `a` is a dependency of the root, so `b` is still the newest.
+    let mut dp = Index::new(&crates).with_versions_order(VersionsOrder::DirectMinimal);
+    let (pub_set, cargo_set) = solutions(&mut dp, root, &ver);
+    assert_eq!(pub_set, cargo_set);
+    assert_eq!(
+        pub_set,
+        [
+            "a@1.0.0",
+            "b@1.1.0",
+            "test_34_direct_and_transitive_versions@0.0.1"
+        ]
+    );
+}
+
+#[test]
+fn prefer_locked_versions() {
+    let (crates, root, ver) = root_from_file("test_34_direct_and_transitive_versions@0.0.1.ron");
+
+    // The locked `a` is kept, while `b` is not locked and is the newest.
+    let mut locked = Lock::default();
+    locked
+        .entry(InternedString::new("a"))
+        .or_default()
+        .insert("1.0.0".parse().unwrap());
+    let mut dp = Index::new(&crates).with_prefer_locked(Some(&locked));
+    let (pub_set, cargo_set) = solutions(&mut dp, root.clone(), &ver);
+    assert_eq!(pub_set, cargo_set);
+    assert_eq!(
+        pub_set,
+        [
+            "a@1.0.0",
+            "b@1.1.0",
+            "test_34_direct_and_transitive_versions@0.0.1"
+        ]
+    );
+
+    // A locked version that does not match is ignored.
+    let mut locked = Lock::default();
+    locked
+        .entry(InternedString::new("a"))
+        .or_default()
+        .insert("2.0.0".parse().unwrap());
+    let mut dp = Index::new(&crates).with_prefer_locked(Some(&locked));
+    let (pub_set, cargo_set) = solutions(&mut dp, root, &ver);
+    assert_eq!(pub_set, cargo_set);
+    assert_eq!(
+        pub_set,
+        [
+            "a@1.1.0",
+            "b@1.1.0",
+            "test_34_direct_and_transitive_versions@0.0.1"
+        ]
+    );
+}
+
+#[test]
+fn root_features_modes() {
+    let (crates, root, ver) = root_from_file("test_37_root_features@0.0.1.ron");
+    // The dependencies selected, without the root itself.
+    let with_root_features = |root_features: RootFeatures| {
+        let mut dp = Index::new(&crates).with_root_features(root_features);
+        let (pub_set, cargo_set) = try_solutions(&mut dp, root.clone(), &ver);
+        assert_eq!(pub_set, cargo_set);
+        pub_set.map(|set| {
+            set.into_iter()
+                .filter(|c| c != "test_37_root_features@0.0.1")
+                .collect_vec()
+        })
+    };
+    let list = |features: &[&str]| {
+        RootFeatures::List(features.iter().map(|f| InternedString::new(f)).collect())
+    };
+    assert_eq!(
+        with_root_features(RootFeatures::All).unwrap(),
+        ["a@0.0.1", "b@0.0.1", "c@0.0.1"]
+    );
+    assert_eq!(
+        with_root_features(RootFeatures::Default).unwrap(),
+        ["a@0.0.1"]
+    );
+    assert!(with_root_features(RootFeatures::NoDefault)
+        .unwrap()
+        .is_empty());
+    // A weak dependency feature does not activate `b`, unlike a dependency feature.
+    assert!(with_root_features(list(&["weak"])).unwrap().is_empty());
+    assert_eq!(
+        with_root_features(list(&["dep_feat"])).unwrap(),
+        ["b@0.0.1"]
+    );
+    assert_eq!(
+        with_root_features(list(&["weak", "dep_feat"])).unwrap(),
+        ["b@0.0.1"]
+    );
+    assert_eq!(
+        with_root_features(list(&["explicit"])).unwrap(),
+        ["c@0.0.1"]
+    );
+    // The implicit feature of an optional dependency.
+    assert_eq!(with_root_features(list(&["a"])).unwrap(), ["a@0.0.1"]);
+    assert_eq!(with_root_features(list(&["no_such_feature"])), None);
+}
+
+#[test]
+fn root_features_modes_agree() {
+    let mut faild = vec![];
+    for file_name in [
+        "test_12_weak_dependencies@0.0.1.ron",
+        "test_13_weak_dependencies@0.0.1.ron",
+        "test_14_weak_dependencies@0.0.1.ron",
+        "test_24_dep_feature@0.0.1.ron",
+        "test_25_dep_feature@0.0.1.ron",
+        "test_26_implicit_feature_with_dep_feature@0.0.1.ron",
+        "test_27_dep_feature_activating_shadowing_feature@0.0.1.ron",
+        "test_28_dep_feature_not_activating_shadowing_feature@0.0.1.ron",
+        "test_37_root_features@0.0.1.ron",
+    ] {
+        let (crates, root, ver) = root_from_file(file_name);
+        let (name, _) = case_from_file_name(file_name);
+        let manifest = &crates[name][&ver].0;
+        let mut modes = vec![
+            RootFeatures::All,
+            RootFeatures::Default,
+            RootFeatures::NoDefault,
+        ];
+        modes.extend(
+            manifest
+                .features
+                .keys()
+                .map(|f| RootFeatures::List(vec![*f])),
+        );
+        for root_features in modes {
+            let mut dp = Index::new(&crates).with_root_features(root_features.clone());
+            let (pub_set, cargo_set) = try_solutions(&mut dp, root.clone(), &ver);
+            if pub_set.is_none() || pub_set != cargo_set {
+                faild.push(format!(
+                    "{file_name} {root_features:?}: {pub_set:?} != {cargo_set:?}"
+                ));
+            }
+        }
+    }
+    assert_eq!(faild.as_slice(), &Vec::<String>::new());
+}
+
+#[test]
+fn features_match_cargo() {
+    let mut faild = vec![];
+    for file_name in [
+        "test_19_same_dep_twice_feature_unification@0.0.1.ron",
+        "test_20_no_implicit_feature@0.1.0.ron",
+        "test_21_implicit_feature@0.1.0.ron",
+        "test_22_missing_explicit_default_feature@0.0.1.ron",
+        "test_23_no_need_for_explicit_default_feature@0.0.1.ron",
+        "test_24_dep_feature@0.0.1.ron",
+        "test_25_dep_feature@0.0.1.ron",
+        "test_26_implicit_feature_with_dep_feature@0.0.1.ron",
+        "test_27_dep_feature_activating_shadowing_feature@0.0.1.ron",
+        "test_28_dep_feature_not_activating_shadowing_feature@0.0.1.ron",
+    ] {
+        let (crates, root, ver) = root_from_file(file_name);
+        let mut dp = Index::new(&crates);
+        let pub_map = resolve(&mut dp, root.clone(), ver.clone());
+        let cargo_map = cargo_resolver::resolve(&root, &ver, &mut dp);
+        match (pub_map, cargo_map) {
+            (Ok(pub_map), Ok(cargo_map)) => {
+                let pub_features = dp.pub_features(&pub_map);
+                let cargo_features = cargo_features(&cargo_map);
+                if pub_features != cargo_features {
+                    faild.push(format!(
+                        "{file_name}: {pub_features:?} != {cargo_features:?}"
+                    ));
+                }
+            }
+            (Err(_), Err(_)) => {}
+            _ => faild.push(format!("{file_name}: only one resolver succeeded")),
+        }
+    }
+    assert_eq!(faild.as_slice(), &Vec::<String>::new());
+}
+
+/// The steps of the cyclic package dependency in PubGrub's solution for a file in `out/index_ron`.
+fn cycle_of_file(file_name: &str) -> Vec<(String, String, String, DependencyKind)> {
+    let (crates, root, ver) = root_from_file(file_name);
+    let mut dp = Index::new(&crates);
+    let pub_map = resolve(&mut dp, root.clone(), ver).unwrap();
+    let Err(CheckError::Cycle(cycle)) = dp.check_cycles(root, &pub_map) else {
+        panic!("{file_name} has no cycle");
+    };
+    cycle
+        .into_iter()
+        .map(|s| {
+            (
+                s.name.to_string(),
+                s.ver.to_string(),
+                s.via.to_string(),
+                s.kind,
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn check_cycles_path() {
+    let step = |name: &str, ver: &str, via: &str| {
+        (
+            name.to_string(),
+            ver.to_string(),
+            via.to_string(),
+            DependencyKind::Normal,
+        )
+    };
+    assert_eq!(
+        cycle_of_file("test_06_cyclic_dependencies@0.1.2.ron"),
+        [
+            step("a", "0.1.1", "test_06_cyclic_dependencies"),
+            step("test_06_cyclic_dependencies", "0.1.2", "a"),
+        ]
+    );
+    // Through optional dependencies, activated by the features the dependencies ask for.
+    assert_eq!(
+        cycle_of_file("test_05_cyclic_optional_dependencies@0.0.1.ron"),
+        [
+            step("async-global-executor", "0.0.1", "io-lifetimes"),
+            step(
+                "io-lifetimes",
+                "0.0.1",
+                "test_05_cyclic_optional_dependencies"
+            ),
+            step(
+                "test_05_cyclic_optional_dependencies",
+                "0.0.1",
+                "async-global-executor"
+            ),
+        ]
+    );
+}